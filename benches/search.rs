@@ -0,0 +1,109 @@
+// Compares the boxed node layout, `BkTree` and `FlatBkTree`: heap taken once built, as seen by the allocator, and
+// range search time. Run with `cargo bench`.
+
+extern crate bkstring;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use bkstring::bknode::BkNode;
+use bkstring::bktree::{BkTree, Dist};
+use bkstring::dist::{hamming_dist, levenshtein_dist};
+
+const WORDS: u32 = 100_000;
+const QUERIES: u32 = 500;
+
+// Keeps track of the bytes and blocks currently allocated.
+struct Counting;
+
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+static BLOCKS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        BLOCKS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        BLOCKS.fetch_sub(1, Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn word(i: u32) -> Vec<u8> {
+    format!("{:08x}", i.wrapping_mul(2_654_435_761)).into_bytes()
+}
+
+// Runs `build`, returning its result with the bytes and blocks it left allocated.
+fn measure<R, F: FnOnce() -> R>(build: F) -> (R, usize, usize) {
+    let (bytes, blocks) = (BYTES.load(Ordering::Relaxed), BLOCKS.load(Ordering::Relaxed));
+    let built = build();
+
+    (built, BYTES.load(Ordering::Relaxed) - bytes, BLOCKS.load(Ordering::Relaxed) - blocks)
+}
+
+// Best time over a few runs of `search` for every query.
+fn time<F: Fn(&[u8], usize) -> usize>(queries: &[Vec<u8>], dist: usize, search: F) -> Duration {
+    (0..3).map(|_| {
+        let start = Instant::now();
+
+        for query in queries {
+            black_box(search(query, dist));
+        }
+
+        start.elapsed()
+    }).min().unwrap()
+}
+
+fn run(name: &str, metric: Dist<u8>, words: &[Vec<u8>], queries: &[Vec<u8>]) {
+    let (boxed, boxed_bytes, boxed_blocks) = measure(|| {
+        let mut root = BkNode::new(words[0].clone(), (), 0);
+
+        for word in &words[1..] {
+            root.insert(word.clone(), (), &metric);
+        }
+
+        root
+    });
+
+    let (tree, tree_bytes, tree_blocks) = measure(|| {
+        let mut tree: BkTree<u8> = BkTree::new(Some(metric));
+
+        for word in words {
+            tree.add(word.clone());
+        }
+
+        tree
+    });
+
+    let (flat, flat_bytes, flat_blocks) = measure(|| tree.to_flat());
+
+    println!("{}: {} words, {} queries", name, words.len(), queries.len());
+    println!("  heap: boxed {} B in {} blocks, BkTree {} B in {} blocks, FlatBkTree {} B in {} blocks",
+             boxed_bytes, boxed_blocks, tree_bytes, tree_blocks, flat_bytes, flat_blocks);
+
+    for dist in 1..4 {
+        let boxed_time = time(queries, dist, |query, dist| boxed.search_iter(query, dist, &metric).count());
+        let tree_time = time(queries, dist, |query, dist| tree.search_iter(query, dist).count());
+        let flat_time = time(queries, dist, |query, dist| flat.search_iter(query, dist).count());
+
+        println!("  search dist {}: boxed {:?}, BkTree {:?}, FlatBkTree {:?}", dist, boxed_time, tree_time, flat_time);
+    }
+}
+
+fn main() {
+    let words: Vec<Vec<u8>> = (0..WORDS).map(word).collect();
+    let queries: Vec<Vec<u8>> = (0..QUERIES).map(|i| word(i * 97 + WORDS)).collect();
+
+    // The Levenshtein distance takes most of the search time, the Hamming distance leaves it to the tree walk.
+    run("levenshtein", levenshtein_dist, &words[..WORDS as usize / 5], &queries[..QUERIES as usize / 5]);
+    run("hamming", hamming_dist, &words, &queries);
+}
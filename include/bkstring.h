@@ -0,0 +1,79 @@
+#ifndef BKSTRING_H
+#define BKSTRING_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// A tree of UTF-8 words. Create it with `bk_tree_new` or `bk_tree_new_with_metric` and release it with
+// `bk_tree_free`.
+typedef struct BkTreeHandle BkTreeHandle;
+
+// A word found by `bk_tree_search`, with its distance from the query.
+typedef struct BkMatch {
+  char *word;
+  size_t distance;
+} BkMatch;
+
+// The matches of one search, owned by the caller until passed to `bk_matches_free`.
+typedef struct BkMatches {
+  struct BkMatch *matches;
+  size_t len;
+} BkMatches;
+
+// Creates an empty tree using the Levenshtein distance.
+struct BkTreeHandle *bk_tree_new(void);
+
+// Creates an empty tree using the built-in metric called `name`: "levenshtein", "hamming", "jaccard" or
+// "modified_jaccard". Returns NULL for any other name.
+//
+// # Safety
+//
+// `name` must be NULL or a NUL-terminated string.
+struct BkTreeHandle *bk_tree_new_with_metric(const char *name);
+
+// Releases a tree. Does nothing if `tree` is NULL.
+//
+// # Safety
+//
+// `tree` must be NULL or a tree returned by this library that was not freed yet.
+void bk_tree_free(struct BkTreeHandle *tree);
+
+// Adds a UTF-8 word. Returns 1 if a new entry was created, 0 if the word was merged into an existing one and -1
+// if `tree` or `word` is NULL or `word` is not valid UTF-8.
+//
+// # Safety
+//
+// `tree` must be NULL or a live tree, and `word` NULL or a NUL-terminated string.
+int bk_tree_add_utf8(struct BkTreeHandle *tree,
+                     const char *word);
+
+// Number of live entries in the tree, 0 if `tree` is NULL.
+//
+// # Safety
+//
+// `tree` must be NULL or a live tree.
+size_t bk_tree_len(const struct BkTreeHandle *tree);
+
+// Finds every entry within `max_dist` of the UTF-8 `word`. Returns NULL if `tree` or `word` is NULL or `word` is
+// not valid UTF-8, otherwise matches that must be released with `bk_matches_free`.
+//
+// # Safety
+//
+// `tree` must be NULL or a live tree, and `word` NULL or a NUL-terminated string.
+struct BkMatches *bk_tree_search(const struct BkTreeHandle *tree,
+                                 const char *word,
+                                 size_t max_dist);
+
+// Releases the matches returned by `bk_tree_search`. Does nothing if `matches` is NULL.
+//
+// # Safety
+//
+// `matches` must be NULL or a result of `bk_tree_search` that was not freed yet.
+void bk_matches_free(struct BkMatches *matches);
+
+#endif  /* BKSTRING_H */
@@ -0,0 +1,109 @@
+# Tests of the Python bindings. Build them into the current environment with `maturin develop`, then run `pytest`.
+
+import pickle
+
+import pytest
+
+import bkstring
+
+WORDS = ["book", "books", "cake", "boo", "boon", "cook", "cape", "cart", "brook", "bark"]
+
+
+def make_tree(**kwargs):
+    tree = bkstring.BkTree(**kwargs)
+    tree.add_list(WORDS)
+    return tree
+
+
+def test_search():
+    tree = make_tree()
+
+    assert len(tree) == len(WORDS)
+    assert sorted(tree.search("bok", 1)) == [("boo", 1), ("book", 1)]
+    assert tree.search("zzzzzz", 2) == []
+    assert sorted(word for word, _ in tree.search("bark", 10)) == sorted(WORDS)
+
+
+def test_closest_and_nearest():
+    tree = make_tree()
+
+    assert tree.find_closest("bookz") == ("book", 1)
+    assert tree.find_closest("zzzzzz", max_dist=1) is None
+    assert [dist for _, dist in tree.nearest("cake", 3)] == [0, 1, 2]
+
+
+def test_add_remove_contains():
+    tree = make_tree()
+
+    assert "cake" in tree
+    assert tree.remove("cake")
+    assert not tree.remove("cake")
+    assert "cake" not in tree
+    assert len(tree) == len(WORDS) - 1
+    assert tree.add("cake")
+
+
+def test_unicode():
+    tree = bkstring.BkTree()
+    tree.add("böok")
+
+    # Distances count code points, not UTF-8 bytes.
+    assert tree.search("book", 1) == [("böok", 1)]
+    assert bkstring.levenshtein_dist("böok", "book") == 1
+    assert bkstring.levenshtein_dist("böok".encode(), b"book") == 2
+
+
+def test_bytes():
+    tree = bkstring.BkTree(binary=True)
+    tree.add_list([word.encode() for word in WORDS])
+
+    assert tree.binary
+    assert sorted(tree.search(b"bok", 1)) == [(b"boo", 1), (b"book", 1)]
+
+    with pytest.raises(TypeError):
+        tree.add("book")
+
+    with pytest.raises(TypeError):
+        make_tree().search(b"bok", 1)
+
+
+def test_metrics():
+    for name in ["levenshtein", "hamming", "jaccard", "modified_jaccard"]:
+        assert bkstring.BkTree(metric=name).metric == name
+        assert bkstring.BkTree(metric=name + "_dist").metric == name
+
+    assert bkstring.hamming_dist("book", "boon") == 1
+    assert bkstring.jaccard_dist("book", "book") == 0
+    assert bkstring.modified_jaccard_dist(b"ab", b"ab") == 0
+
+    tree = make_tree(metric="hamming_dist")
+    assert sorted(tree.search("boot", 1)) == [("book", 1), ("boon", 1)]
+
+    with pytest.raises(ValueError):
+        bkstring.BkTree(metric="cosine")
+
+    with pytest.raises(TypeError):
+        bkstring.levenshtein_dist("book", b"book")
+
+
+def test_pickle():
+    binary = bkstring.BkTree(metric="hamming", binary=True)
+    binary.add_list([b"book", b"cook"])
+
+    for tree in [make_tree(), binary]:
+        copy = pickle.loads(pickle.dumps(tree))
+
+        assert copy.metric == tree.metric
+        assert copy.binary == tree.binary
+        assert repr(copy) == repr(tree)
+
+    tree = make_tree()
+    tree.remove("cart")
+    copy = pickle.loads(pickle.dumps(tree))
+
+    assert sorted(copy.search("bok", 2)) == sorted(tree.search("bok", 2))
+    assert "cart" not in copy
+    assert len(copy) == len(tree)
+
+    with pytest.raises(ValueError):
+        copy.__setstate__(b"nope")
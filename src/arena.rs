@@ -0,0 +1,351 @@
+// Storage of a tree in a few flat buffers rather than one allocation per node. Nodes sit in one array and are
+// referred to by index, their words share one buffer and so do their child lists, where the children of a node are
+// contiguous and in edge distance order. Each child is stored with its edge distance, so picking the children to
+// visit only reads the list itself.
+//
+// A child list that runs out of room moves to the end of its buffer with twice the room, and a replaced word is
+// appended if it does not fit where the old one was, so a tree that changes a lot leaves unused space behind, as do
+// the nodes it drops. `packed` copies the nodes still reachable into fresh buffers, breadth first.
+
+use std::mem;
+
+use search::NodeRef;
+
+#[derive(Clone, Copy)]
+struct Slot {
+    word_start: usize,
+    word_len: usize,
+    children_start: usize,
+    children_len: usize,
+    children_cap: usize,
+    count: usize,
+    deleted: bool,
+}
+
+// An entry of a child list.
+#[derive(Clone, Copy, Default)]
+pub struct Child {
+    // Distance to the parent node.
+    pub dist: usize,
+    pub idx: usize,
+}
+
+#[derive(Clone)]
+pub struct Arena<T> {
+    slots: Vec<Slot>,
+    words: Vec<T>,
+    children: Vec<Child>,
+    root: usize,
+}
+
+// A node of an arena, usable with the algorithms of `search`.
+pub struct ArenaRef<'a, T: 'a> {
+    arena: &'a Arena<T>,
+    idx: usize,
+    edge: usize,
+}
+
+impl<'a, T> Clone for ArenaRef<'a, T> {
+    fn clone(&self) -> ArenaRef<'a, T> {
+        *self
+    }
+}
+
+impl<'a, T> Copy for ArenaRef<'a, T> {}
+
+impl<'a, T> ArenaRef<'a, T> {
+    pub(crate) fn idx(&self) -> usize {
+        self.idx
+    }
+}
+
+impl<'a, T> NodeRef<'a, T> for ArenaRef<'a, T> {
+    fn word(&self) -> &'a [T] {
+        self.arena.word(self.idx)
+    }
+
+    fn edge(&self) -> usize {
+        self.edge
+    }
+
+    fn is_deleted(&self) -> bool {
+        self.arena.is_deleted(self.idx)
+    }
+
+    fn count(&self) -> usize {
+        self.arena.count(self.idx)
+    }
+
+    fn child_count(&self) -> usize {
+        self.arena.slots[self.idx].children_len
+    }
+
+    fn child(&self, idx: usize) -> ArenaRef<'a, T> {
+        let child = self.arena.children(self.idx)[idx];
+
+        ArenaRef { arena: self.arena, idx: child.idx, edge: child.dist }
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Arena<T> {
+        Arena::new()
+    }
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Arena<T> {
+        Arena {
+            slots: vec![],
+            words: vec![],
+            children: vec![],
+            root: 0
+        }
+    }
+
+    // Number of nodes, including any that are no longer reachable from the root.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    // The root node, or `None` if no node was added yet.
+    pub fn root(&self) -> Option<ArenaRef<'_, T>> {
+        match self.slots.is_empty() {
+            true => None,
+            false => Some(ArenaRef { arena: self, idx: self.root, edge: 0 })
+        }
+    }
+
+    pub fn root_idx(&self) -> usize {
+        self.root
+    }
+
+    pub fn set_root(&mut self, idx: usize) {
+        self.root = idx;
+    }
+
+    pub fn word(&self, idx: usize) -> &[T] {
+        let slot = &self.slots[idx];
+
+        &self.words[slot.word_start..slot.word_start + slot.word_len]
+    }
+
+    pub fn count(&self, idx: usize) -> usize {
+        self.slots[idx].count
+    }
+
+    pub fn set_count(&mut self, idx: usize, count: usize) {
+        self.slots[idx].count = count;
+    }
+
+    pub fn is_deleted(&self, idx: usize) -> bool {
+        self.slots[idx].deleted
+    }
+
+    pub fn set_deleted(&mut self, idx: usize, deleted: bool) {
+        self.slots[idx].deleted = deleted;
+    }
+
+    pub fn children(&self, idx: usize) -> &[Child] {
+        let slot = &self.slots[idx];
+
+        &self.children[slot.children_start..slot.children_start + slot.children_len]
+    }
+
+    // Adds a node without children, returning its index. It is not attached to any other node.
+    pub fn push<I: IntoIterator<Item = T>>(&mut self, word: I) -> usize {
+        let word_start = self.words.len();
+
+        self.words.extend(word);
+        self.slots.push(Slot {
+            word_start,
+            word_len: self.words.len() - word_start,
+            children_start: 0,
+            children_len: 0,
+            children_cap: 0,
+            count: 1,
+            deleted: false
+        });
+
+        self.slots.len() - 1
+    }
+
+    // Replaces the word of a node, in place if the new one is no longer.
+    pub fn set_word(&mut self, idx: usize, word: Vec<T>) {
+        let slot = &mut self.slots[idx];
+
+        match word.len() <= slot.word_len {
+            true => {
+                slot.word_len = word.len();
+
+                for (symbol, new) in self.words[slot.word_start..].iter_mut().zip(word) {
+                    *symbol = new;
+                }
+            },
+            false => {
+                slot.word_start = self.words.len();
+                slot.word_len = word.len();
+                self.words.extend(word);
+            }
+        };
+    }
+
+    // Makes room for `cap` children below a node that has none.
+    pub fn reserve_children(&mut self, idx: usize, cap: usize) {
+        let slot = &mut self.slots[idx];
+
+        debug_assert_eq!(slot.children_len, 0);
+
+        if cap > slot.children_cap {
+            slot.children_start = self.children.len();
+            slot.children_cap = cap;
+            self.children.resize(self.children.len() + cap, Child::default());
+        }
+    }
+
+    // Position of the child at edge distance `dist` in the child list of `idx`, or where such a child would go.
+    pub fn find_child(&self, idx: usize, dist: usize) -> Result<usize, usize> {
+        self.children(idx).binary_search_by(|child| child.dist.cmp(&dist))
+    }
+
+    pub fn insert_child(&mut self, idx: usize, pos: usize, child: Child) {
+        let slot = self.slots[idx];
+
+        if slot.children_len == slot.children_cap {
+            let cap = (slot.children_cap * 2).max(1);
+            let start = self.children.len();
+
+            self.children.extend_from_within(slot.children_start..slot.children_start + slot.children_len);
+            self.children.resize(start + cap, Child::default());
+            self.slots[idx].children_start = start;
+            self.slots[idx].children_cap = cap;
+        }
+
+        let slot = &mut self.slots[idx];
+        let list = &mut self.children[slot.children_start..slot.children_start + slot.children_len + 1];
+
+        list.copy_within(pos..list.len() - 1, pos + 1);
+        list[pos] = child;
+        slot.children_len += 1;
+    }
+
+    // Appends a child, which must be at a larger edge distance than the others.
+    pub fn push_child(&mut self, idx: usize, child: Child) {
+        let pos = self.slots[idx].children_len;
+
+        self.insert_child(idx, pos, child);
+    }
+
+    // Puts node `child` in place of the child at `pos`, at the same edge distance.
+    pub fn replace_child(&mut self, idx: usize, pos: usize, child: usize) {
+        let start = self.slots[idx].children_start;
+
+        self.children[start + pos].idx = child;
+    }
+
+    pub fn remove_child(&mut self, idx: usize, pos: usize) {
+        let slot = &mut self.slots[idx];
+
+        self.children.copy_within(slot.children_start + pos + 1..slot.children_start + slot.children_len, slot.children_start + pos);
+        slot.children_len -= 1;
+    }
+
+    // Detaches every child of a node, keeping the room they took for new ones.
+    pub fn clear_children(&mut self, idx: usize) {
+        self.slots[idx].children_len = 0;
+    }
+
+    // Every node of the subtree under `idx`, tombstones included, in pre-order.
+    pub fn subtree(&self, idx: usize) -> Vec<usize> {
+        let mut nodes = vec![];
+        let mut stack = vec![idx];
+
+        while let Some(idx) = stack.pop() {
+            nodes.push(idx);
+            // Reversed so children come out in edge distance order.
+            stack.extend(self.children(idx).iter().rev().map(|child| child.idx));
+        }
+
+        nodes
+    }
+
+    // Makes room for `nodes` more nodes with words of the current average length, each moving one child list.
+    pub fn reserve(&mut self, nodes: usize) {
+        let word_len = self.words.len() / self.slots.len().max(1);
+        let list_len = self.children.len() / self.slots.len().max(1);
+
+        self.slots.reserve_exact(nodes);
+        self.words.reserve_exact(nodes * word_len);
+        self.children.reserve_exact(nodes * (2 * list_len + 1));
+    }
+
+    // Bytes allocated for nodes, words and child lists, unused room included.
+    pub fn heap_size(&self) -> usize {
+        self.slots.capacity() * mem::size_of::<Slot>() + self.words.capacity() * mem::size_of::<T>()
+            + self.children.capacity() * mem::size_of::<Child>()
+    }
+}
+
+impl<T: Clone> Arena<T> {
+    // Adds a copy of node `idx` of `source`, without its children, returning its index.
+    pub fn copy_node(&mut self, source: &Arena<T>, idx: usize) -> usize {
+        let copy = self.push(source.word(idx).iter().cloned());
+
+        self.slots[copy].count = source.count(idx);
+        self.slots[copy].deleted = source.is_deleted(idx);
+        copy
+    }
+
+    // Adds a copy of the subtree under node `idx` of `source`, returning the index of its root.
+    pub fn copy_subtree(&mut self, source: &Arena<T>, idx: usize) -> usize {
+        let top = self.copy_node(source, idx);
+        let mut stack = vec![(idx, top)];
+
+        while let Some((source_idx, copy)) = stack.pop() {
+            self.reserve_children(copy, source.children(source_idx).len());
+
+            for &child in source.children(source_idx) {
+                let child_copy = self.copy_node(source, child.idx);
+
+                self.push_child(copy, Child { dist: child.dist, idx: child_copy });
+                stack.push((child.idx, child_copy));
+            }
+        }
+
+        top
+    }
+
+    // A copy of the nodes reachable from the root, laid out breadth first without any unused room, so the children of
+    // a node are next to each other in every buffer.
+    pub fn packed(&self) -> Arena<T> {
+        let mut packed = Arena::new();
+
+        if self.slots.is_empty() {
+            return packed;
+        }
+
+        let mut queue = vec![Child { dist: 0, idx: self.root }];
+        let mut next = 0;
+
+        while next < queue.len() {
+            let children = self.children(queue[next].idx);
+            let copy = packed.copy_node(self, queue[next].idx);
+
+            // Nodes are numbered in the order they are queued, and the root is not in any child list.
+            packed.slots[copy].children_start = queue.len() - 1;
+            packed.slots[copy].children_len = children.len();
+            packed.slots[copy].children_cap = children.len();
+            queue.extend(children);
+            next += 1;
+        }
+
+        packed.children = queue[1..].iter().enumerate().map(|(pos, child)| Child { dist: child.dist, idx: pos + 1 }).collect();
+        packed.slots.shrink_to_fit();
+        packed.words.shrink_to_fit();
+        packed
+    }
+}
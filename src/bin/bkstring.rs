@@ -0,0 +1,367 @@
+// Command-line tool to build indexes of word lists and query them.
+//
+// An index file holds the name of its metric, followed by the tree in the format of `BkTree::write_to`:
+//
+//     name_len     u64       length of the metric name
+//     name         name_len  bytes of the name, see `BuiltinMetric::name`
+//     tree                   `BkTree::write_to`
+
+extern crate bkstring;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::process;
+use std::thread;
+
+use bkstring::bktree::BkTree;
+use bkstring::codec::{invalid_data, read_u64, write_u64};
+use bkstring::dist::BuiltinMetric;
+
+const USAGE: &str = "usage:
+    bkstring build [--metric NAME] --output INDEX [WORDS]
+    bkstring query --index INDEX --max-dist N WORD
+    bkstring stats --index INDEX
+    bkstring match --index INDEX --max-dist N [--format tsv|jsonl] [--threads N]
+
+build reads one word per line from WORDS, or stdin if it is omitted or -. Metrics are levenshtein (the default),
+hamming, jaccard and modified_jaccard.
+
+query prints each entry within N of WORD and its distance, separated by a tab, closest first.
+
+match reads lines from stdin and writes each one with its closest entry within N and their distance, leaving both
+empty (tsv) or null (jsonl) when there is none. Output lines are in input order. Invalid UTF-8 in a line is replaced
+with U+FFFD.
+
+Tab-separated output escapes backslash, tab, newline and carriage return in words and lines
+as \\\\, \\t, \\n and \\r.";
+
+// Lines read from stdin before they are split between threads and matched.
+const MATCH_BATCH: usize = 4096;
+
+// Longest metric name accepted when reading an index, so a corrupt length fails early.
+const MAX_NAME_LEN: usize = 64;
+
+type Index = BkTree<char, BuiltinMetric>;
+
+// Options given as `--name value` and the remaining arguments, in order.
+struct Args {
+    options: Vec<(String, String)>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Args, String> {
+        let mut parsed = Args { options: vec![], positional: vec![] };
+
+        while let Some(arg) = args.next() {
+            match arg.starts_with("--") {
+                true => {
+                    let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+
+                    parsed.options.push((arg[2..].to_string(), value));
+                },
+                false => parsed.positional.push(arg)
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    // Checks that only options listed in `known` and at most `max_positional` other arguments were given.
+    fn expect(&self, known: &[&str], max_positional: usize) -> Result<(), String> {
+        if let Some((name, _)) = self.options.iter().find(|(name, _)| !known.contains(&name.as_str())) {
+            return Err(format!("unknown option --{}", name));
+        }
+
+        match self.positional.len() > max_positional {
+            true => Err(format!("unexpected argument {}", self.positional[max_positional])),
+            false => Ok(())
+        }
+    }
+
+    // The last value given for option `name`.
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.iter().rev().find(|(option, _)| option == name).map(|(_, value)| value.as_str())
+    }
+
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.option(name).ok_or_else(|| format!("missing --{}", name))
+    }
+
+    fn number(&self, name: &str) -> Result<usize, String> {
+        let value = self.required(name)?;
+
+        value.parse().map_err(|_| format!("invalid --{}: {}", name, value))
+    }
+}
+
+fn read_words<R: BufRead>(reader: R) -> io::Result<Vec<Vec<char>>> {
+    let mut words = vec![];
+
+    for line in reader.lines() {
+        let line = line?;
+
+        if !line.is_empty() {
+            words.push(line.chars().collect());
+        }
+    }
+
+    Ok(words)
+}
+
+fn write_index<W: Write>(tree: &Index, mut writer: W) -> io::Result<()> {
+    let name = tree.metric().name();
+
+    write_u64(&mut writer, name.len())?;
+    writer.write_all(name.as_bytes())?;
+    tree.write_to(&mut writer)?;
+    writer.flush()
+}
+
+fn read_index<R: Read>(mut reader: R) -> io::Result<Index> {
+    let len = read_u64(&mut reader)?;
+
+    if len > MAX_NAME_LEN {
+        return Err(invalid_data("not a bkstring index"));
+    }
+
+    let mut name = vec![0; len];
+
+    reader.read_exact(&mut name)?;
+
+    let metric = String::from_utf8(name).ok()
+        .and_then(|name| BuiltinMetric::from_name(&name))
+        .ok_or_else(|| invalid_data("unknown metric in index"))?;
+
+    BkTree::read_from(reader, metric)
+}
+
+fn open_index(path: &str) -> Result<Index, String> {
+    File::open(path)
+        .and_then(|file| read_index(BufReader::new(file)))
+        .map_err(|err| format!("{}: {}", path, err))
+}
+
+fn build(args: &Args) -> Result<(), String> {
+    args.expect(&["metric", "output"], 1)?;
+
+    let name = args.option("metric").unwrap_or("levenshtein");
+    let metric = BuiltinMetric::from_name(name).ok_or_else(|| format!("unknown metric: {}", name))?;
+    let output = args.required("output")?;
+
+    let words = match args.positional.first().map(String::as_str) {
+        None | Some("-") => read_words(io::stdin().lock()).map_err(|err| format!("stdin: {}", err))?,
+        Some(path) => File::open(path)
+            .and_then(|file| read_words(BufReader::new(file)))
+            .map_err(|err| format!("{}: {}", path, err))?
+    };
+
+    let tree = BkTree::build_with_metric(words, metric);
+
+    File::create(output)
+        .and_then(|file| write_index(&tree, BufWriter::new(file)))
+        .map_err(|err| format!("{}: {}", output, err))?;
+
+    eprintln!("{} entries written to {}", tree.len(), output);
+    Ok(())
+}
+
+fn query(args: &Args) -> Result<(), String> {
+    args.expect(&["index", "max-dist"], 1)?;
+
+    let tree = open_index(args.required("index")?)?;
+    let max_dist = args.number("max-dist")?;
+    let word: Vec<char> = args.positional.first().ok_or("missing WORD")?.chars().collect();
+
+    let mut found = tree.search_with_distance(&word, max_dist);
+
+    found.sort_by(|(first, first_dist), (second, second_dist)| (first_dist, first).cmp(&(second_dist, second)));
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    for (word, dist) in found {
+        writeln!(out, "{}\t{}", tsv_field(&word.iter().collect::<String>()), dist).map_err(|err| err.to_string())?;
+    }
+
+    out.flush().map_err(|err| err.to_string())
+}
+
+fn stats(args: &Args) -> Result<(), String> {
+    args.expect(&["index"], 0)?;
+
+    let tree = open_index(args.required("index")?)?;
+
+    println!("metric: {}", tree.metric().name());
+    println!("entries: {}", tree.len());
+    print!("{}", tree.stats());
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Tsv,
+    JsonLines,
+}
+
+// A JSON string literal holding `text`.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+
+    quoted.push('"');
+
+    for ch in text.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => quoted.push(ch)
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+// `text` with the characters that would break up a TSV line escaped.
+fn tsv_field(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for ch in text.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            ch => escaped.push(ch)
+        }
+    }
+
+    escaped
+}
+
+// Reads the next line without its line ending, replacing invalid UTF-8 rather than failing, or `None` at the end.
+fn read_line<R: BufRead>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<Option<String>> {
+    buf.clear();
+
+    if reader.read_until(b'\n', buf)? == 0 {
+        return Ok(None);
+    }
+
+    if buf.ends_with(b"\n") {
+        buf.pop();
+
+        if buf.ends_with(b"\r") {
+            buf.pop();
+        }
+    }
+
+    Ok(Some(String::from_utf8_lossy(buf).into_owned()))
+}
+
+fn write_match<W: Write>(out: &mut W, format: Format, line: &str, found: Option<(Vec<char>, usize)>) -> io::Result<()> {
+    let found = found.map(|(word, dist)| (word.into_iter().collect::<String>(), dist));
+
+    match (format, found) {
+        (Format::Tsv, Some((word, dist))) => writeln!(out, "{}\t{}\t{}", tsv_field(line), tsv_field(&word), dist),
+        (Format::Tsv, None) => writeln!(out, "{}\t\t", tsv_field(line)),
+        (Format::JsonLines, Some((word, dist))) => {
+            writeln!(out, "{{\"line\":{},\"match\":{},\"distance\":{}}}", json_string(line), json_string(&word), dist)
+        },
+        (Format::JsonLines, None) => writeln!(out, "{{\"line\":{},\"match\":null,\"distance\":null}}", json_string(line))
+    }
+}
+
+// Finds the closest entry to each line, splitting the lines between `threads` threads.
+fn match_batch(tree: &Index, lines: &[String], max_dist: usize, threads: usize) -> Vec<Option<(Vec<char>, usize)>> {
+    let chunk = lines.len().div_ceil(threads).max(1);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = lines.chunks(chunk).map(|chunk| scope.spawn(move || {
+            chunk.iter().map(|line| {
+                let word: Vec<char> = line.chars().collect();
+
+                tree.find_closest(&word, Some(max_dist))
+            }).collect::<Vec<_>>()
+        })).collect();
+
+        // Joined in spawn order, so results line up with `lines`.
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+    })
+}
+
+fn match_lines(args: &Args) -> Result<(), String> {
+    args.expect(&["index", "max-dist", "format", "threads"], 0)?;
+
+    let tree = open_index(args.required("index")?)?;
+    let max_dist = args.number("max-dist")?;
+    let format = match args.option("format").unwrap_or("tsv") {
+        "tsv" => Format::Tsv,
+        "jsonl" => Format::JsonLines,
+        format => return Err(format!("unknown format: {}", format))
+    };
+    let threads = match args.option("threads") {
+        Some(_) => args.number("threads")?.max(1),
+        None => thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1)
+    };
+
+    let stdin = io::stdin();
+    let mut reader = stdin.lock();
+    let mut buf = vec![];
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    loop {
+        let mut batch = Vec::with_capacity(MATCH_BATCH);
+
+        while batch.len() < MATCH_BATCH {
+            match read_line(&mut reader, &mut buf).map_err(|err| format!("stdin: {}", err))? {
+                Some(line) => batch.push(line),
+                None => break
+            };
+        }
+
+        if batch.is_empty() {
+            break;
+        }
+
+        for (line, found) in batch.iter().zip(match_batch(&tree, &batch, max_dist, threads)) {
+            write_match(&mut out, format, line, found).map_err(|err| err.to_string())?;
+        }
+
+        // Flushed per batch so results reach the next stage of a pipeline as they are ready.
+        out.flush().map_err(|err| err.to_string())?;
+    }
+
+    Ok(())
+}
+
+fn run() -> Result<(), String> {
+    let mut args = env::args().skip(1);
+    let command = args.next();
+    let args = Args::parse(args)?;
+
+    match command.as_deref() {
+        Some("build") => build(&args),
+        Some("query") => query(&args),
+        Some("stats") => stats(&args),
+        Some("match") => match_lines(&args),
+        Some("help") | Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            Ok(())
+        },
+        Some(command) => Err(format!("unknown command {}\n\n{}", command, USAGE)),
+        None => Err(USAGE.to_string())
+    }
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("bkstring: {}", err);
+        process::exit(1);
+    }
+}
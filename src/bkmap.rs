@@ -0,0 +1,291 @@
+use bknode::_BkNode;
+use bktree::Dist;
+use dist::*;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serde")]
+use serde::de::Error;
+
+// A BK tree where every key carries a value, e.g. a user name mapped to its user id.
+#[derive(Clone)]
+pub struct _BkMap<K, V, M = Dist<K>> {
+    _root: Option<_BkNode<K, V>>,
+    dist: M,
+    len: usize
+}
+
+impl<K: Eq + Sized, V> _BkMap<K, V> {
+    pub fn new(func: Option<Dist<K>>) -> _BkMap<K, V> {
+        _BkMap::with_metric(func.unwrap_or(levenshtein_dist))
+    }
+}
+
+impl<K: Eq + Sized, V, M: Metric<K>> _BkMap<K, V, M> {
+    pub fn with_metric(metric: M) -> _BkMap<K, V, M> {
+        _BkMap {
+            _root: None,
+            dist: metric,
+            len: 0
+        }
+    }
+
+    pub fn metric(&self) -> &M {
+        &self.dist
+    }
+
+    // Inserts `value` under `key`, returning the previous value if the key was already present.
+    pub fn insert(&mut self, key: Vec<K>, value: V) -> Option<V> {
+        let dist = &self.dist;
+        let mut node = match self._root {
+            Some(ref mut root) => root,
+            None => {
+                self._root = Some(_BkNode::new(key, value, 0));
+                self.len += 1;
+                return None;
+            }
+        };
+
+        loop {
+            let curr_dist = dist.distance(&node.word, &key);
+
+            if curr_dist == 0 && node.word == key {
+                return Some(::std::mem::replace(&mut node.value, value));
+            }
+
+            match node.children.binary_search_by(|probe| probe.dist.cmp(&curr_dist)) {
+                Ok(child_idx) => node = &mut node.children[child_idx],
+                Err(insert_idx) => {
+                    node.children.insert(insert_idx, _BkNode::new(key, value, curr_dist));
+                    self.len += 1;
+                    return None;
+                }
+            };
+        }
+    }
+
+    pub fn get(&self, key: &[K]) -> Option<&V> {
+        match self._root {
+            Some(ref root) => root.find(key, &self.dist).map(|node| &node.value),
+            None => None
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &[K]) -> Option<&mut V> {
+        match self._root {
+            Some(ref mut root) => root.find_mut(key, &self.dist).map(|node| &mut node.value),
+            None => None
+        }
+    }
+
+    pub fn contains_key(&self, key: &[K]) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Returns every entry within `dist` of `key`.
+    pub fn search(&self, key: &[K], dist: usize) -> Vec<(&[K], &V, usize)> {
+        match self._root {
+            Some(ref root) => root.search_iter(key, dist, &self.dist)
+                .map(|(node, dist)| (&node.word[..], &node.value, dist))
+                .collect(),
+            None => vec![]
+        }
+    }
+
+    // Returns up to `k` entries closest to `key`, ordered by increasing distance.
+    pub fn nearest(&self, key: &[K], k: usize) -> Vec<(&[K], &V, usize)> {
+        match self._root {
+            Some(ref root) => root.nearest(key, k, &self.dist).into_iter()
+                .map(|(node, dist)| (&node.word[..], &node.value, dist))
+                .collect(),
+            None => vec![]
+        }
+    }
+
+    // Returns the entry closest to `key`, or `None` if the map is empty or every entry is farther than `max_dist`.
+    pub fn find_closest(&self, key: &[K], max_dist: Option<usize>) -> Option<(&[K], &V, usize)> {
+        match self._root {
+            Some(ref root) => root.find_closest(key, max_dist.unwrap_or(usize::MAX), &self.dist)
+                .map(|(node, dist)| (&node.word[..], &node.value, dist)),
+            None => None
+        }
+    }
+}
+
+// The serialized form of a map, with the metric stored by name and the entries as the records of its root node.
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct MapRef<'a, K: 'a, V: 'a> {
+    metric: &'a str,
+    root: &'a Option<_BkNode<K, V>>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct MapOwned<K, V> {
+    metric: String,
+    root: Option<_BkNode<K, V>>,
+}
+
+#[cfg(feature = "serde")]
+impl<K: Serialize, V: Serialize, M: NamedMetric<K>> Serialize for _BkMap<K, V, M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MapRef { metric: self.dist.name(), root: &self._root }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K: Deserialize<'de>, V: Deserialize<'de>, M: NamedMetric<K>> Deserialize<'de> for _BkMap<K, V, M> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<_BkMap<K, V, M>, D::Error> {
+        let map = MapOwned::<K, V>::deserialize(deserializer)?;
+
+        let metric = match M::from_name(&map.metric) {
+            Some(metric) => metric,
+            None => return Err(D::Error::custom(format!("unknown metric {:?}", map.metric)))
+        };
+
+        let mut len = 0;
+        let mut stack: Vec<&_BkNode<K, V>> = map.root.iter().collect();
+
+        while let Some(node) = stack.pop() {
+            len += 1;
+            stack.extend(node.children.iter());
+        }
+
+        Ok(_BkMap { _root: map.root, dist: metric, len })
+    }
+}
+
+pub type BkMap<K, V, M = Dist<K>> = _BkMap<K, V, M>;
+
+#[test]
+fn insert_get_test() {
+    let mut m: BkMap<char, u32> = BkMap::new(None);
+    let alice: Vec<char> = "alice".chars().collect();
+    let alicia: Vec<char> = "alicia".chars().collect();
+
+    assert!(m.is_empty());
+    assert_eq!(m.get(&alice), None);
+
+    assert_eq!(m.insert(alice.clone(), 1), None);
+    assert_eq!(m.insert(alicia.clone(), 2), None);
+    assert_eq!(m.insert("bob".chars().collect(), 3), None);
+    assert_eq!(m.len(), 3);
+
+    assert_eq!(m.get(&alice), Some(&1));
+    assert_eq!(m.get(&alicia), Some(&2));
+    assert_eq!(m.get(&"alic".chars().collect::<Vec<char>>()), None);
+
+    assert_eq!(m.insert(alicia.clone(), 20), Some(2));
+    assert_eq!(m.get(&alicia), Some(&20));
+    assert_eq!(m.len(), 3);
+
+    *m.get_mut(&alice).unwrap() += 10;
+    assert_eq!(m.get(&alice), Some(&11));
+    assert!(m.contains_key(&alice));
+}
+
+#[test]
+fn zero_distance_keys_test() {
+    // Jaccard distance is zero for different keys over the same set of symbols, both must still be kept.
+    let mut m: BkMap<char, u32> = BkMap::new(Some(jaccard_dist));
+    let gg: Vec<char> = "GG".chars().collect();
+    let gggg: Vec<char> = "GGGG".chars().collect();
+
+    assert_eq!(m.insert(gg.clone(), 1), None);
+    assert_eq!(m.insert(gggg.clone(), 2), None);
+    assert_eq!(m.get(&gg), Some(&1));
+    assert_eq!(m.get(&gggg), Some(&2));
+    assert_eq!(m.search(&gg, 0).len(), 2);
+}
+
+#[test]
+fn map_search_test() {
+    let mut m: BkMap<char, usize> = BkMap::new(None);
+    let names = TEST_WORDS;
+
+    for (id, name) in names.iter().enumerate() {
+        m.insert(name.chars().collect(), id);
+    }
+
+    let query: Vec<char> = "bok".chars().collect();
+    let results = m.search(&query, 1);
+    let mut ids: Vec<usize> = results.iter().map(|&(_, &id, _)| id).collect();
+    ids.sort();
+
+    assert_eq!(ids, vec![0, 3]);
+    assert!(results.iter().all(|&(key, &id, dist)| key == &names[id].chars().collect::<Vec<char>>()[..] && dist == 1));
+
+    let nearest = m.nearest(&"carts".chars().collect::<Vec<char>>(), 2);
+
+    assert_eq!(nearest.len(), 2);
+    assert_eq!(nearest[0], (&"cart".chars().collect::<Vec<char>>()[..], &7, 1));
+    assert_eq!(nearest[1].2, 3);
+
+    assert_eq!(m.find_closest(&"brookx".chars().collect::<Vec<char>>(), None).map(|(_, &id, dist)| (id, dist)), Some((8, 1)));
+    assert_eq!(m.find_closest(&"xyzzyx".chars().collect::<Vec<char>>(), Some(2)), None);
+    assert!(BkMap::<char, usize>::new(None).nearest(&query, 3).is_empty());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn map_serde_test() {
+    use serde_json;
+
+    let mut m: BkMap<char, usize, BuiltinMetric> = BkMap::with_metric(BuiltinMetric::Levenshtein);
+
+    for (id, name) in TEST_WORDS.iter().enumerate() {
+        m.insert(name.chars().collect(), id);
+    }
+
+    let json = serde_json::to_string(&m).unwrap();
+
+    // Nodes are written as a flat list of records, the root first.
+    assert!(json.starts_with("{\"metric\":\"levenshtein\",\"root\":[{\"word\":[\"b\",\"o\",\"o\",\"k\"],\"dist\":0,\"value\":0,\"children\":"));
+
+    let n: BkMap<char, usize, BuiltinMetric> = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(n.len(), m.len());
+    assert_eq!(*n.metric(), BuiltinMetric::Levenshtein);
+
+    for (id, name) in TEST_WORDS.iter().enumerate() {
+        assert_eq!(n.get(&name.chars().collect::<Vec<char>>()), Some(&id));
+    }
+
+    let query: Vec<char> = "bok".chars().collect();
+    let mut expected = m.search(&query, 2);
+    let mut found = n.search(&query, 2);
+
+    expected.sort();
+    found.sort();
+    assert_eq!(found, expected);
+
+    let empty: BkMap<char, usize, BuiltinMetric> = serde_json::from_str("{\"metric\":\"hamming\",\"root\":null}").unwrap();
+
+    assert!(empty.is_empty());
+    assert!(serde_json::from_str::<BkMap<char, usize, BuiltinMetric>>(&json.replace("levenshtein", "jaro")).is_err());
+
+    // A single node, then the same node with a child count that the records do not back, or an extra record.
+    let node = "{\"word\":[\"a\"],\"dist\":0,\"value\":1,\"children\":0}";
+    let root = |records: String| serde_json::from_str::<_BkNode<char, ()>>(&records.replace("\"value\":1", "\"value\":null"));
+
+    assert_eq!(root(format!("[{}]", node)).unwrap().word, vec!['a']);
+    assert!(root(format!("[{}]", node.replace("\"children\":0", "\"children\":1"))).is_err());
+    assert!(root(format!("[{},{}]", node, node)).is_err());
+    assert!(root("[]".to_string()).is_err());
+
+    // Children must come in increasing edge distance order.
+    let parent = node.replace("\"children\":0", "\"children\":2");
+    let child = |dist: usize| node.replace("\"dist\":0", &format!("\"dist\":{}", dist));
+
+    assert!(root(format!("[{},{},{}]", parent, child(1), child(2))).is_ok());
+    assert!(root(format!("[{},{},{}]", parent, child(2), child(1))).is_err());
+}
@@ -1,68 +1,291 @@
 use std::default::Default;
 use std::cmp::Ordering;
 
-#[derive(Clone)]
-pub struct _BkNode<T> {
+use dist::Metric;
+use search::{self, NodeRef, SearchIter};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serde")]
+use serde::de::Error;
+
+pub struct _BkNode<T, V = ()> {
     pub word: Vec<T>,
     pub dist: usize,
-    pub children: Vec<_BkNode<T>>,
+    pub children: Vec<_BkNode<T, V>>,
+    pub value: V,
+}
+
+// What to do when a word is inserted at distance 0 from a live entry.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DuplicatePolicy {
+    // Keep the existing entry and drop the new word.
+    Ignore,
+    // Overwrite the existing entry with the new word.
+    Replace,
+    // Keep the existing entry and increment its count.
+    Count,
+    // Insert the new word as a separate child at distance 0.
+    #[default]
+    KeepAll,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertOutcome {
+    // A new node was created for the word.
+    New,
+    // A removed entry at distance 0 was brought back with the new word.
+    Revived,
+    // The word was merged into an existing entry according to the duplicate policy.
+    Duplicate,
+}
+
+// Cloning and dropping are done with an explicit stack, as the derived versions recurse once per tree level.
+impl<T: Clone, V: Clone> Clone for _BkNode<T, V> {
+    fn clone(&self) -> _BkNode<T, V> {
+        let shallow = |node: &_BkNode<T, V>| _BkNode {
+            word: node.word.clone(),
+            dist: node.dist,
+            children: Vec::with_capacity(node.children.len()),
+            value: node.value.clone()
+        };
+
+        // Source node, its copy so far and the index of the next child to copy.
+        let mut stack = vec![(self, shallow(self), 0)];
+
+        loop {
+            let next = {
+                let &mut (source, _, ref mut child_idx) = stack.last_mut().unwrap();
+
+                *child_idx += 1;
+                source.children.get(*child_idx - 1)
+            };
+
+            match next {
+                Some(child) => stack.push((child, shallow(child), 0)),
+                None => {
+                    let (_, copy, _) = stack.pop().unwrap();
+
+                    match stack.last_mut() {
+                        Some(&mut (_, ref mut parent, _)) => parent.children.push(copy),
+                        None => return copy
+                    };
+                }
+            };
+        }
+    }
+}
+
+impl<T, V> Drop for _BkNode<T, V> {
+    fn drop(&mut self) {
+        let mut stack = ::std::mem::take(&mut self.children);
+
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
+        }
+    }
 }
 
-impl<T> PartialEq for _BkNode<T> {
-    fn eq(&self, other: &_BkNode<T>) -> bool {
+impl<T, V> PartialEq for _BkNode<T, V> {
+    fn eq(&self, other: &_BkNode<T, V>) -> bool {
         self.dist == other.dist
     }
 }
 
-impl<T> Eq for _BkNode<T> {}
+impl<T, V> Eq for _BkNode<T, V> {}
 
-impl<T> Ord for _BkNode<T> {
-    fn cmp(&self, other: &_BkNode<T>) -> Ordering {
+impl<T, V> Ord for _BkNode<T, V> {
+    fn cmp(&self, other: &_BkNode<T, V>) -> Ordering {
         self.dist.cmp(&other.dist)
     }
 }
 
-impl<T> PartialOrd for _BkNode<T> {
-    fn partial_cmp(&self, other: &_BkNode<T>) -> Option<Ordering> {
+impl<T, V> PartialOrd for _BkNode<T, V> {
+    fn partial_cmp(&self, other: &_BkNode<T, V>) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl<T: Clone + Sized> _BkNode<T> {
-    pub fn add(&mut self, word: Vec<T>, dist: fn(Vec<T>, Vec<T>) -> usize) {
-        // Handle the initial node case.
-        if self.word.is_empty() {
-            self.word = word;
-            return;
+impl<'a, T, V> NodeRef<'a, T> for &'a _BkNode<T, V> {
+    fn word(&self) -> &'a [T] {
+        &self.word
+    }
+
+    fn edge(&self) -> usize {
+        self.dist
+    }
+
+    fn is_deleted(&self) -> bool {
+        false
+    }
+
+    fn count(&self) -> usize {
+        1
+    }
+
+    fn child_count(&self) -> usize {
+        self.children.len()
+    }
+
+    fn child(&self, idx: usize) -> &'a _BkNode<T, V> {
+        &self.children[idx]
+    }
+}
+
+impl<T: Sized, V> _BkNode<T, V> {
+    pub fn new(word: Vec<T>, value: V, dist: usize) -> _BkNode<T, V> {
+        _BkNode {
+            word,
+            dist,
+            children: vec![],
+            value
         }
+    }
 
-        let curr_dist = dist(self.word.to_owned(), word.to_owned());
-
-        // Binary search returns the index of the search value, or the index where, if the value is inserted, it will retain ordering.
-        match self.children.binary_search_by(|curr_node| curr_node.dist.cmp(&curr_dist)) {
-            Ok(child_idx) => {
-                self.children[child_idx].add(word.to_owned(), dist);
-            },
-            Err(insert_idx) => {
-                let node = _BkNode {
-                    word: word.to_owned(),
-                    dist: curr_dist,
-                    children: vec![]
-                };
+    pub fn insert<M: Metric<T>>(&mut self, word: Vec<T>, value: V, metric: &M) {
+        let mut parent = self;
+
+        loop {
+            let curr_dist = metric.distance(&parent.word, &word);
+
+            // Binary search returns the index of the search value, or the index where, if the value is inserted, it will retain ordering.
+            match parent.children.binary_search_by(|curr_node| curr_node.dist.cmp(&curr_dist)) {
+                Ok(child_idx) => parent = &mut parent.children[child_idx],
+                Err(insert_idx) => {
+                    parent.children.insert(insert_idx, _BkNode::new(word, value, curr_dist));
+                    return;
+                }
+            };
+        }
+    }
 
-                self.children.insert(insert_idx, node);
+    // Returns the node holding exactly `word`, following the single edge that can lead to it at each level.
+    pub fn find<M: Metric<T>>(&self, word: &[T], metric: &M) -> Option<&_BkNode<T, V>> where T: Eq {
+        let mut node = self;
+
+        loop {
+            let curr_dist = metric.distance(&node.word, word);
+
+            if curr_dist == 0 && node.word == word {
+                return Some(node);
             }
-        };
+
+            match node.children.binary_search_by(|probe| probe.dist.cmp(&curr_dist)) {
+                Ok(child_idx) => node = &node.children[child_idx],
+                Err(_) => return None
+            };
+        }
+    }
+
+    pub fn find_mut<M: Metric<T>>(&mut self, word: &[T], metric: &M) -> Option<&mut _BkNode<T, V>> where T: Eq {
+        let mut node = self;
+
+        loop {
+            let curr_dist = metric.distance(&node.word, word);
+
+            if curr_dist == 0 && node.word == word {
+                return Some(node);
+            }
+
+            match node.children.binary_search_by(|probe| probe.dist.cmp(&curr_dist)) {
+                Ok(child_idx) => node = &mut node.children[child_idx],
+                Err(_) => return None
+            };
+        }
+    }
+
+    // Lazily yields every node of this subtree within `max_dist` of `word`, paired with its distance.
+    pub fn search_iter<'a, 'q, M: Metric<T>>(&'a self, word: &'q [T], max_dist: usize, metric: &'q M) -> SearchIter<'q, T, &'a _BkNode<T, V>, M> {
+        SearchIter::new(self, word, max_dist, metric)
+    }
+
+    // Returns up to `k` nodes closest to `word`, ordered by increasing distance.
+    pub fn nearest<M: Metric<T>>(&self, word: &[T], k: usize, metric: &M) -> Vec<(&_BkNode<T, V>, usize)> {
+        search::nearest(self, word, k, metric)
+    }
+
+    // Returns the node closest to `word`, pruning with the best distance found so far.
+    pub fn find_closest<M: Metric<T>>(&self, word: &[T], max_dist: usize, metric: &M) -> Option<(&_BkNode<T, V>, usize)> {
+        search::find_closest(self, word, max_dist, metric)
     }
 }
 
-impl<T> Default for _BkNode<T> {
-    fn default() -> _BkNode<T> {
+impl<T, V: Default> Default for _BkNode<T, V> {
+    fn default() -> _BkNode<T, V> {
         _BkNode {
             word: vec![],
             dist: 0,
-            children: vec![]
+            children: vec![],
+            value: Default::default()
+        }
+    }
+}
+
+// The serialized form of a node, as a flat list of records for it and its descendants in pre-order, like the node
+// records of `codec`, so deep trees need no recursion either way. `word` and `value` are borrowed when serializing.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct NodeRecord<W, V> {
+    word: W,
+    dist: usize,
+    value: V,
+    children: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<T: Serialize, V: Serialize> Serialize for _BkNode<T, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut stack = vec![self];
+
+        serializer.collect_seq(::std::iter::from_fn(move || {
+            let node = stack.pop()?;
+
+            // Pushed in reverse so children come out in edge distance order.
+            stack.extend(node.children.iter().rev());
+
+            Some(NodeRecord { word: &node.word, dist: node.dist, value: &node.value, children: node.children.len() })
+        }))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>, V: Deserialize<'de>> Deserialize<'de> for _BkNode<T, V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<_BkNode<T, V>, D::Error> {
+        let records = Vec::<NodeRecord<Vec<T>, V>>::deserialize(deserializer)?;
+        let count = records.len();
+        // Nodes whose children are still being read, with the number of children left.
+        let mut stack: Vec<(_BkNode<T, V>, usize)> = vec![];
+
+        for (pos, record) in records.into_iter().enumerate() {
+            if record.children > count - pos - 1 {
+                return Err(D::Error::custom("truncated node records"));
+            }
+
+            if let Some((parent, _)) = stack.last() {
+                if parent.children.last().is_some_and(|last| last.dist >= record.dist) {
+                    return Err(D::Error::custom("children out of edge distance order"));
+                }
+            }
+
+            stack.push((_BkNode::new(record.word, record.value, record.dist), record.children));
+
+            // Attach every node that has all of its children to its parent, the root is returned once complete.
+            while let Some(&(_, 0)) = stack.last() {
+                let (node, _) = stack.pop().unwrap();
+
+                match stack.last_mut() {
+                    Some(&mut (ref mut parent, ref mut left)) => {
+                        parent.children.push(node);
+                        *left -= 1;
+                    },
+                    None if pos + 1 == count => return Ok(node),
+                    None => return Err(D::Error::custom("trailing node records"))
+                };
+            }
         }
+
+        Err(D::Error::custom("truncated node records"))
     }
 }
 
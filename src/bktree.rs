@@ -1,78 +1,767 @@
+use std::collections::BTreeMap;
 use std::default::Default;
+use std::io::{self, Read, Write};
 
-use bknode::BkNode;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::index;
+
+use arena::{Arena, ArenaRef, Child};
+use bknode::{DuplicatePolicy, InsertOutcome};
+use codec::*;
 use dist::*;
+use search::{self, NodeRef, SearchIter, SearchStats};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serde")]
+use serde::de::Error;
 
-pub type Dist<T> = fn(Vec<T>, Vec<T>) -> usize;
+pub type Dist<T> = fn(&[T], &[T]) -> usize;
 
+// Nodes are kept in an `Arena`, see there for the layout.
 #[derive(Clone)]
-pub struct _BkTree<T> {
-    _root: BkNode<T>,
-    dist: Dist<T>
+pub struct _BkTree<T, M = Dist<T>> {
+    arena: Arena<T>,
+    dist: M,
+    nodes: usize,
+    tombstones: usize,
+    // Number of nodes when the arena was last packed.
+    packed_nodes: usize,
+    policy: DuplicatePolicy
 }
 
 impl<T: Eq + Clone + Sized> _BkTree<T> {
-    pub extern fn new(func: Option<Dist<T>>) -> _BkTree<T> {
+    pub fn new(func: Option<Dist<T>>) -> _BkTree<T> {
+        match func {
+            Some(func) => _BkTree::with_metric(func),
+            None => _BkTree::with_metric(levenshtein_dist)
+        }
+    }
+
+    // Builds a tree from `list` in one go, see `build_with_metric`.
+    pub fn build(list: Vec<Vec<T>>, func: Option<Dist<T>>) -> _BkTree<T> {
         match func {
-            Some(func) => _BkTree {
-                _root: Default::default(),
-                dist: func
+            Some(func) => _BkTree::build_with_metric(list, func),
+            None => _BkTree::build_with_metric(list, levenshtein_dist)
+        }
+    }
+}
+
+impl<T: Eq + Clone + Sized, M: Metric<T>> _BkTree<T, M> {
+    pub fn with_metric(metric: M) -> _BkTree<T, M> {
+        _BkTree {
+            arena: Arena::new(),
+            dist: metric,
+            nodes: 0,
+            tombstones: 0,
+            packed_nodes: 0,
+            policy: Default::default()
+        }
+    }
+
+    pub fn metric(&self) -> &M {
+        &self.dist
+    }
+
+    // Sets how words at distance 0 from an existing entry are handled. Defaults to `DuplicatePolicy::KeepAll`.
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.policy = policy;
+    }
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.policy
+    }
+
+    // Returns whether a new entry was created for `word`.
+    pub fn add(&mut self, word: Vec<T>) -> bool {
+        // The first entry becomes the root, which may be the empty word like any other.
+        if self.nodes == 0 {
+            self.arena = Arena::new();
+            self.arena.push(word);
+            self.nodes = 1;
+            self.packed_nodes = 1;
+            return true;
+        }
+
+        match self.insert(word) {
+            InsertOutcome::New => {
+                self.nodes += 1;
+
+                if self.nodes > self.pack_limit() {
+                    self.pack();
+                }
+
+                true
+            },
+            InsertOutcome::Revived => {
+                self.tombstones -= 1;
+                true
             },
-            None => _BkTree {
-                _root: Default::default(),
-                dist: levenshtein_dist
+            InsertOutcome::Duplicate => false
+        }
+    }
+
+    // Inserts `word` below the root, handling an existing entry at distance 0 according to the duplicate policy.
+    fn insert(&mut self, word: Vec<T>) -> InsertOutcome {
+        let mut idx = self.arena.root_idx();
+
+        loop {
+            let curr_dist = self.dist.distance(self.arena.word(idx), &word);
+
+            if curr_dist == 0 && self.policy != DuplicatePolicy::KeepAll {
+                if self.arena.is_deleted(idx) {
+                    self.arena.set_word(idx, word);
+                    self.arena.set_deleted(idx, false);
+                    self.arena.set_count(idx, 1);
+                    return InsertOutcome::Revived;
+                }
+
+                match self.policy {
+                    DuplicatePolicy::Replace => self.arena.set_word(idx, word),
+                    DuplicatePolicy::Count => {
+                        let count = self.arena.count(idx);
+
+                        self.arena.set_count(idx, count + 1);
+                    },
+                    _ => {}
+                };
+
+                return InsertOutcome::Duplicate;
             }
+
+            match self.arena.find_child(idx, curr_dist) {
+                Ok(pos) => idx = self.arena.children(idx)[pos].idx,
+                Err(pos) => {
+                    let child = self.arena.push(word);
+
+                    self.arena.insert_child(idx, pos, Child { dist: curr_dist, idx: child });
+                    return InsertOutcome::New;
+                }
+            };
+        }
+    }
+
+    // Places the detached node `idx` in the subtree under `from`, keeping its word and count. Any children it had are
+    // discarded.
+    fn place(&mut self, from: usize, idx: usize) {
+        let mut parent = from;
+
+        self.arena.clear_children(idx);
+
+        loop {
+            let curr_dist = self.dist.distance(self.arena.word(parent), self.arena.word(idx));
+
+            match self.arena.find_child(parent, curr_dist) {
+                Ok(pos) => parent = self.arena.children(parent)[pos].idx,
+                Err(pos) => {
+                    self.arena.insert_child(parent, pos, Child { dist: curr_dist, idx });
+                    return;
+                }
+            };
         }
     }
 
-    pub extern fn add(&mut self, word: Vec<T>) {
-        self._root.add(word, self.dist);
+    // Returns the live node holding exactly `word`, following the single edge that can lead to it at each level.
+    fn find(&self, word: &[T]) -> Option<usize> {
+        let mut idx = self.root()?.idx();
+
+        loop {
+            let curr_dist = self.dist.distance(self.arena.word(idx), word);
+
+            if curr_dist == 0 && !self.arena.is_deleted(idx) && self.arena.word(idx) == word {
+                return Some(idx);
+            }
+
+            match self.arena.find_child(idx, curr_dist) {
+                Ok(pos) => idx = self.arena.children(idx)[pos].idx,
+                Err(_) => return None
+            };
+        }
     }
 
-    pub extern fn add_list(&mut self, list: Vec<Vec<T>>) {
+    pub fn add_list(&mut self, list: Vec<Vec<T>>) {
         for word in list {
             self.add(word);
         }
     }
 
-    fn r_search(&self, node: &BkNode<T>, word: Vec<T>, dist: usize, s_list: &mut Vec<Vec<T>>) {
-        let curr_word = &node.word;
-        let curr_dist = (self.dist)(curr_word.to_owned(), word.to_owned());
+    // Builds a tree from `list` in one go. Unlike `add_list`, the word of each node is chosen among sampled candidates
+    // rather than taken in input order. This usually gives shallower trees that prune better, most of all when word
+    // lengths vary, but on words of a single length it can do slightly worse than `add_list`. Duplicates are kept as with
+    // `DuplicatePolicy::KeepAll`, the policy only applies to later inserts.
+    //
+    // Candidates are sampled with a fixed seed, so the same list always gives the same tree, see `build_with_rng`.
+    pub fn build_with_metric(list: Vec<Vec<T>>, metric: M) -> _BkTree<T, M> {
+        _BkTree::build_with_rng(list, metric, &mut StdRng::seed_from_u64(BUILD_SEED))
+    }
+
+    // Same as `build_with_metric`, sampling pivot candidates with `rng`.
+    pub fn build_with_rng<R: Rng>(list: Vec<Vec<T>>, metric: M, rng: &mut R) -> _BkTree<T, M> {
+        let mut tree = _BkTree::with_metric(metric);
 
-        let min_dist = match curr_dist > dist {
-            true => curr_dist - dist,
-            false => {
-                s_list.push(curr_word.to_owned());
-                0
+        if !list.is_empty() {
+            tree.nodes = list.len();
+            tree.packed_nodes = list.len();
+            tree.arena = build_subtree(list, &tree.dist, rng);
+        }
+
+        tree
+    }
+
+    // Number of live entries in the tree.
+    pub fn len(&self) -> usize {
+        self.nodes - self.tombstones
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Number of edges on the longest path from the root, 0 for an empty tree or a single entry.
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut stack: Vec<(usize, usize)> = self.root().into_iter().map(|root| (root.idx(), 0)).collect();
+
+        while let Some((idx, level)) = stack.pop() {
+            depth = depth.max(level);
+            stack.extend(self.arena.children(idx).iter().map(|child| (child.idx, level + 1)));
+        }
+
+        depth
+    }
+
+    // Largest number of children below a single node.
+    pub fn max_fanout(&self) -> usize {
+        let mut fanout = 0;
+        let mut stack: Vec<usize> = self.root().into_iter().map(|root| root.idx()).collect();
+
+        while let Some(idx) = stack.pop() {
+            fanout = fanout.max(self.arena.children(idx).len());
+            stack.extend(self.arena.children(idx).iter().map(|child| child.idx));
+        }
+
+        fanout
+    }
+
+    // The root node, or `None` for an empty tree.
+    pub fn root(&self) -> Option<ArenaRef<'_, T>> {
+        match self.nodes {
+            0 => None,
+            _ => self.arena.root()
+        }
+    }
+
+    pub(crate) fn arena(&self) -> &Arena<T> {
+        &self.arena
+    }
+
+    // Bytes allocated for the nodes, including room left by earlier changes until the next compaction.
+    pub fn heap_size(&self) -> usize {
+        self.arena.heap_size()
+    }
+
+    // Number of times `word` was inserted, which only exceeds 1 under `DuplicatePolicy::Count`.
+    pub fn count(&self, word: &[T]) -> usize {
+        match self.find(word) {
+            Some(idx) => self.arena.count(idx),
+            None => 0
+        }
+    }
+
+    // Removes one entry equal to `word`, returning whether it was found.
+    // The node is only marked as deleted so it can keep routing searches; the tree is compacted once over half of its nodes are tombstones.
+    pub fn remove(&mut self, word: &[T]) -> bool {
+        let idx = match self.find(word) {
+            Some(idx) => idx,
+            None => return false
+        };
+
+        match self.arena.count(idx) {
+            1 => self.arena.set_deleted(idx, true),
+            count => {
+                self.arena.set_count(idx, count - 1);
+                return true;
             }
         };
 
-        let max_dist = curr_dist + dist + 1;
+        self.tombstones += 1;
+
+        if self.tombstones * 2 > self.nodes {
+            self.compact();
+        }
+
+        true
+    }
+
+    // Drops every tombstone, rebuilding only the subtrees rooted at removed entries, then packs the nodes left.
+    pub fn compact(&mut self) {
+        if self.tombstones == 0 {
+            return;
+        }
+
+        let root = self.arena.root_idx();
+
+        if self.arena.is_deleted(root) {
+            let live = self.live_nodes(root);
+
+            self.nodes = live.len();
+            self.tombstones = 0;
+
+            match live.split_first() {
+                Some((&first, rest)) => {
+                    self.arena.clear_children(first);
+                    self.arena.set_root(first);
+
+                    for &idx in rest {
+                        self.place(first, idx);
+                    }
+                },
+                None => {
+                    self.arena = Arena::new();
+                    return;
+                }
+            };
+        } else {
+            let mut stack = vec![root];
+
+            while let Some(parent) = stack.pop() {
+                self.compact_children(parent);
+                // Rebuilt subtrees hold no tombstones, walking them again only costs a few comparisons.
+                stack.extend(self.arena.children(parent).iter().map(|child| child.idx));
+            }
+        }
+
+        self.pack();
+    }
+
+    // Copies the nodes into fresh buffers, see `Arena::packed`. Inserts leave new nodes and grown child lists at the
+    // end of the buffers, away from their neighbours in the tree, so this is done again once the tree grew by a quarter.
+    // Inserts stay amortised constant time and most nodes keep the layout of a fresh copy.
+    fn pack(&mut self) {
+        self.packed_nodes = self.nodes;
+        self.arena = self.arena.packed();
+        // Room for the nodes added until the next pack, so the buffers do not double in size in between.
+        self.arena.reserve(self.pack_limit() - self.nodes);
+    }
+
+    // Number of nodes above which the tree is packed again.
+    fn pack_limit(&self) -> usize {
+        self.packed_nodes + self.packed_nodes / 4 + 1
+    }
+
+    // Rebuilds the subtrees rooted at the direct children of `parent` that are tombstones.
+    // Any word of such a subtree is at the same edge distance from `parent`, so the first live word found can take the tombstone's place.
+    fn compact_children(&mut self, parent: usize) {
+        let mut pos = 0;
+
+        while pos < self.arena.children(parent).len() {
+            let child = self.arena.children(parent)[pos].idx;
+
+            if !self.arena.is_deleted(child) {
+                pos += 1;
+                continue;
+            }
+
+            let live = self.live_nodes(child);
+            let dropped = self.arena.subtree(child).len() - live.len();
+
+            self.nodes -= dropped;
+            self.tombstones -= dropped;
+
+            match live.split_first() {
+                Some((&first, rest)) => {
+                    self.arena.clear_children(first);
+                    self.arena.replace_child(parent, pos, first);
+
+                    for &idx in rest {
+                        self.place(first, idx);
+                    }
+
+                    pos += 1;
+                },
+                None => self.arena.remove_child(parent, pos)
+            };
+        }
+    }
+
+    // The live nodes of the subtree under `idx`, in pre-order.
+    fn live_nodes(&self, idx: usize) -> Vec<usize> {
+        self.arena.subtree(idx).into_iter().filter(|&idx| !self.arena.is_deleted(idx)).collect()
+    }
+
+    // Moves every entry of `other` into this tree, handing `other` back untouched if its metric differs. Function
+    // pointers are compared by address.
+    // Under `DuplicatePolicy::KeepAll`, trees whose roots are at distance 0 are grafted together, so only the subtrees
+    // hanging at an edge distance used by both are rebuilt. Otherwise every live entry of `other` is inserted again.
+    pub fn merge(&mut self, other: _BkTree<T, M>) -> Result<(), _BkTree<T, M>> where M: PartialEq {
+        if self.dist != other.dist {
+            return Err(other);
+        }
+
+        let other_root = match other.root() {
+            Some(root) => root.idx(),
+            None => return Ok(())
+        };
+
+        let keep_all = self.policy == DuplicatePolicy::KeepAll;
+
+        if keep_all && self.nodes == 0 {
+            self.arena = other.arena;
+            self.nodes = other.nodes;
+            self.tombstones = other.tombstones;
+            self.packed_nodes = other.packed_nodes;
+            return Ok(());
+        }
+
+        if keep_all && self.dist.distance(self.arena.word(self.arena.root_idx()), other.arena.word(other_root)) == 0 {
+            self.nodes += other.nodes;
+            self.tombstones += other.tombstones;
+            self.graft(&other.arena);
+        } else {
+            for idx in other.live_nodes(other_root) {
+                self.merge_entry(other.arena.word(idx).to_vec(), other.arena.count(idx));
+            }
+        }
+
+        if self.tombstones * 2 > self.nodes {
+            self.compact();
+        } else if self.nodes > self.pack_limit() {
+            self.pack();
+        }
+
+        Ok(())
+    }
+
+    // Copies the nodes of `source`, whose root word is at distance 0 from this tree's, below the root.
+    // Children of a source node at an edge distance that is free in the target are copied over whole, those matching a
+    // child at distance 0 are grafted in turn and the others are placed again node by node. The source node itself
+    // becomes a duplicate entry. Entries are never merged, so this only fits `DuplicatePolicy::KeepAll`.
+    fn graft(&mut self, source: &Arena<T>) {
+        // Pairs of a target node and a source node at distance 0 from it.
+        let mut stack = vec![(self.arena.root_idx(), source.root_idx())];
+
+        while let Some((target, from)) = stack.pop() {
+            for &child in source.children(from) {
+                match self.arena.find_child(target, child.dist) {
+                    Err(pos) => {
+                        let copy = self.arena.copy_subtree(source, child.idx);
+
+                        self.arena.insert_child(target, pos, Child { dist: child.dist, idx: copy });
+                    },
+                    Ok(pos) => {
+                        let existing = self.arena.children(target)[pos].idx;
+
+                        if self.dist.distance(self.arena.word(existing), source.word(child.idx)) == 0 {
+                            stack.push((existing, child.idx));
+                            continue;
+                        }
+
+                        for idx in source.subtree(child.idx) {
+                            let copy = self.arena.copy_node(source, idx);
+
+                            self.place(existing, copy);
+                        }
+                    }
+                };
+            }
 
-        let min_idx = match node.children.binary_search_by(|probe| probe.dist.cmp(&min_dist)) {
-            Ok(idx) => idx,
-            Err(idx) => idx
+            let copy = self.arena.copy_node(source, from);
+
+            self.place(target, copy);
+        }
+    }
+
+    // Adds an entry that was inserted `count` times into another tree.
+    fn merge_entry(&mut self, word: Vec<T>, count: usize) {
+        if self.policy != DuplicatePolicy::Count {
+            for _ in 1..count {
+                self.add(word.clone());
+            }
+
+            self.add(word);
+            return;
+        }
+
+        self.add(word.clone());
+
+        if let Some(idx) = self.find(&word) {
+            let total = self.arena.count(idx) + count - 1;
+
+            self.arena.set_count(idx, total);
+        }
+    }
+
+    pub fn search(&self, word: &[T], dist: usize) -> Vec<Vec<T>> {
+        self.search_with_distance(word, dist).into_iter().map(|(word, _)| word).collect()
+    }
+
+    // Same as `search`, but each match is paired with its distance from the query word.
+    pub fn search_with_distance(&self, word: &[T], dist: usize) -> Vec<(Vec<T>, usize)> {
+        self.search_iter(word, dist).map(|(word, dist)| (word.to_owned(), dist)).collect()
+    }
+
+    // Same as `search_with_distance`, also reporting how much work the search did.
+    pub fn search_with_stats(&self, word: &[T], dist: usize) -> (Vec<(Vec<T>, usize)>, SearchStats) {
+        let root = match self.is_empty() {
+            true => return (vec![], Default::default()),
+            false => self.root().unwrap()
         };
 
-        let max_idx = match node.children.binary_search_by(|probe| probe.dist.cmp(&max_dist)) {
-            Ok(idx) => idx,
-            Err(idx) => idx
+        let (nodes, stats) = search::search_with_stats(root, word, dist, &self.dist);
+
+        (nodes.into_iter().map(|(node, dist)| (node.word().to_owned(), dist)).collect(), stats)
+    }
+
+    // Same as `search_with_distance`, but matches are yielded lazily as the tree is walked, so stopping early skips the rest of the work.
+    pub fn search_iter<'a>(&'a self, word: &'a [T], dist: usize) -> impl Iterator<Item = (&'a [T], usize)> + 'a {
+        let nodes = match self.is_empty() {
+            true => None,
+            false => self.root().map(|root| SearchIter::new(root, word, dist, &self.dist))
         };
 
-        for i in min_idx..max_idx {
-            self.r_search(&node.children[i], word.to_owned(), dist, s_list);
+        nodes.into_iter().flatten().map(|(node, dist)| (node.word(), dist))
+    }
+
+    // Returns the single entry closest to `word`, or `None` if the tree is empty or every entry is farther than `max_dist`.
+    pub fn find_closest(&self, word: &[T], max_dist: Option<usize>) -> Option<(Vec<T>, usize)> {
+        if self.is_empty() {
+            return None;
+        }
+
+        search::find_closest(self.root()?, word, max_dist.unwrap_or(usize::MAX), &self.dist)
+            .map(|(node, dist)| (node.word().to_owned(), dist))
+    }
+
+    // Returns up to `k` entries closest to `word`, ordered by increasing distance.
+    pub fn nearest(&self, word: &[T], k: usize) -> Vec<(Vec<T>, usize)> {
+        match self.root() {
+            Some(root) if !self.is_empty() => search::nearest(root, word, k, &self.dist).into_iter()
+                .map(|(node, dist)| (node.word().to_owned(), dist))
+                .collect(),
+            _ => vec![]
         }
     }
+}
+
+// Number of pivot candidates tried for a subtree, and of its words each candidate is scored against.
+const PIVOT_CANDIDATES: usize = 8;
+const PIVOT_SAMPLE: usize = 32;
+
+// Seed of the pivot sampling done by `build_with_metric`.
+const BUILD_SEED: u64 = 0x626b_7374;
+
+// Builds a subtree holding every word of the non-empty `words`, its root being the first node of the returned arena.
+// Nodes are created top-down with an explicit stack, each subtree being finished before the next sibling is started,
+// so every child list is filled in order and takes exactly the room it needs.
+fn build_subtree<T, M: Metric<T>, R: Rng>(words: Vec<Vec<T>>, metric: &M, rng: &mut R) -> Arena<T> {
+    let mut arena = Arena::new();
+    // Words of the subtrees still to build, with their edge distance and their parent, `None` for the root.
+    let mut pending = vec![(words, 0, None)];
 
-    pub extern fn search(&self, word: Vec<T>, dist: usize) -> Vec<Vec<T>> {
-        let mut results: Vec<Vec<T>> = vec![];
+    while let Some((mut words, edge, parent)) = pending.pop() {
+        let pivot = choose_pivot(&words, metric, rng);
+        let word = words.swap_remove(pivot);
+        let mut buckets: BTreeMap<usize, Vec<Vec<T>>> = BTreeMap::new();
 
-        self.r_search(&self._root, word.clone(), dist, &mut results);
-        return results;
+        for other in words {
+            buckets.entry(metric.distance(&word, &other)).or_default().push(other);
+        }
+
+        let idx = arena.push(word);
+
+        arena.reserve_children(idx, buckets.len());
+
+        if let Some(parent) = parent {
+            arena.push_child(parent, Child { dist: edge, idx });
+        }
+
+        // Reversed so the closest bucket is built first.
+        pending.extend(buckets.into_iter().rev().map(|(dist, words)| (words, dist, Some(idx))));
     }
+
+    arena
 }
 
-pub type BkTree<T> = _BkTree<T>;
+// Returns the index of the word of `words` that spreads a sample of the others most evenly over their edge distances.
+// Small subtrees just take their first word, as scoring would cost more than it saves.
+fn choose_pivot<T, M: Metric<T>, R: Rng>(words: &[Vec<T>], metric: &M, rng: &mut R) -> usize {
+    if words.len() <= PIVOT_SAMPLE {
+        return 0;
+    }
+
+    let candidates = index::sample(rng, words.len(), PIVOT_CANDIDATES);
+    let sample = index::sample(rng, words.len(), PIVOT_SAMPLE);
+
+    candidates.into_iter().min_by_key(|&candidate| {
+        let mut buckets: BTreeMap<usize, usize> = BTreeMap::new();
+
+        for idx in sample.iter().filter(|&idx| idx != candidate) {
+            *buckets.entry(metric.distance(&words[candidate], &words[idx])).or_default() += 1;
+        }
+
+        // A search only descends into the few buckets near the query distance, so small, even buckets prune best.
+        buckets.values().map(|size| size * size).sum::<usize>()
+    }).unwrap()
+}
+
+impl<T: Eq + Clone + Sized + Symbol, M: Metric<T>> _BkTree<T, M> {
+    // Writes the tree in the binary format described in `codec`, keeping the exact node layout.
+    // Writes are small, so pass a buffered writer for files.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        FORMAT_VERSION.write_symbol(&mut writer)?;
+        policy_to_byte(self.policy).write_symbol(&mut writer)?;
+        write_u64(&mut writer, self.nodes)?;
+        write_u64(&mut writer, self.tombstones)?;
+
+        for record in self.root().into_iter().flat_map(node_records) {
+            write_u64(&mut writer, record.dist)?;
+            record.flags.write_symbol(&mut writer)?;
+            write_u64(&mut writer, record.count)?;
+            write_u64(&mut writer, record.word.len())?;
+
+            for symbol in record.word {
+                symbol.write_symbol(&mut writer)?;
+            }
+
+            write_u64(&mut writer, record.children)?;
+        }
+
+        Ok(())
+    }
+
+    // Reads a tree written by `write_to`. The metric is not stored in the file, `metric` must be the one the tree was built with.
+    pub fn read_from<R: Read>(mut reader: R, metric: M) -> io::Result<_BkTree<T, M>> {
+        let mut magic = [0; 4];
+
+        reader.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(invalid_data("not a BK tree file"));
+        }
+
+        if u32::read_symbol(&mut reader)? != FORMAT_VERSION {
+            return Err(invalid_data("unsupported BK tree format version"));
+        }
+
+        let mut tree = _BkTree::with_metric(metric);
+
+        tree.policy = policy_from_byte(u8::read_symbol(&mut reader)?)?;
+        tree.nodes = read_u64(&mut reader)?;
+        tree.tombstones = read_u64(&mut reader)?;
+
+        if tree.tombstones > tree.nodes {
+            return Err(invalid_data("more tombstones than nodes"));
+        }
+
+        let mut builder = TreeBuilder::new(tree.nodes);
+
+        for _ in 0..tree.nodes {
+            let dist = read_u64(&mut reader)?;
+            let flags = u8::read_symbol(&mut reader)?;
+            let count = read_u64(&mut reader)?;
+            let word_len = read_u64(&mut reader)?;
+            let mut word = Vec::with_capacity(::std::cmp::min(word_len, 1024));
+
+            for _ in 0..word_len {
+                word.push(T::read_symbol(&mut reader)?);
+            }
+
+            let children = read_u64(&mut reader)?;
+
+            builder.push(NodeRecord { word, dist, flags, count, children }).map_err(invalid_data)?;
+        }
+
+        if let Some(arena) = builder.finish(tree.tombstones).map_err(invalid_data)? {
+            tree.arena = arena;
+            tree.packed_nodes = tree.nodes;
+        }
+
+        Ok(tree)
+    }
+}
+
+// The serialized form of a tree, with the metric stored by name and the nodes as a flat list of records in pre-order,
+// as in the binary format, so deep trees need no recursion either way.
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct TreeRef<'a, T: 'a> {
+    metric: &'a str,
+    policy: DuplicatePolicy,
+    nodes: usize,
+    tombstones: usize,
+    records: RecordsRef<'a, T>,
+}
+
+#[cfg(feature = "serde")]
+struct RecordsRef<'a, T: 'a>(Option<ArenaRef<'a, T>>);
+
+#[cfg(feature = "serde")]
+impl<'a, T: Serialize> Serialize for RecordsRef<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.into_iter().flat_map(node_records))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct TreeOwned<T> {
+    metric: String,
+    policy: DuplicatePolicy,
+    nodes: usize,
+    tombstones: usize,
+    records: Vec<NodeRecord<Vec<T>>>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: Serialize, M: NamedMetric<T>> Serialize for _BkTree<T, M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        TreeRef {
+            metric: self.dist.name(),
+            policy: self.policy,
+            nodes: self.nodes,
+            tombstones: self.tombstones,
+            records: RecordsRef(match self.nodes {
+                0 => None,
+                _ => self.arena.root()
+            }),
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>, M: NamedMetric<T>> Deserialize<'de> for _BkTree<T, M> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<_BkTree<T, M>, D::Error> {
+        let tree = TreeOwned::<T>::deserialize(deserializer)?;
+
+        let metric = match M::from_name(&tree.metric) {
+            Some(metric) => metric,
+            None => return Err(D::Error::custom(format!("unknown metric {:?}", tree.metric)))
+        };
+
+        if tree.records.len() != tree.nodes || tree.tombstones > tree.nodes {
+            return Err(D::Error::custom("node counts do not match the tree"));
+        }
+
+        let mut builder = TreeBuilder::new(tree.nodes);
+
+        for record in tree.records {
+            builder.push(record).map_err(D::Error::custom)?;
+        }
+
+        Ok(_BkTree {
+            arena: builder.finish(tree.tombstones).map_err(D::Error::custom)?.unwrap_or_default(),
+            dist: metric,
+            nodes: tree.nodes,
+            tombstones: tree.tombstones,
+            packed_nodes: tree.nodes,
+            policy: tree.policy
+        })
+    }
+}
+
+pub type BkTree<T, M = Dist<T>> = _BkTree<T, M>;
 
 #[test]
 fn add_list_test() {
@@ -84,9 +773,9 @@ fn add_list_test() {
     let test2: Vec<char> = "bar".chars().collect();
     let test3: Vec<char> = "baz".chars().collect();
 
-    assert_eq!(b._root.word, test1);
-    assert_eq!(b._root.children[0].word, test2);
-    assert_eq!(b._root.children[0].children[0].word, test3);
+    assert_eq!(b.root().unwrap().word(), test1);
+    assert_eq!(b.root().unwrap().child(0).word(), test2);
+    assert_eq!(b.root().unwrap().child(0).child(0).word(), test3);
 }
 
 #[test]
@@ -102,7 +791,7 @@ fn search_test() {
     b.add("fooda".chars().collect());
 
     {
-        let list = b.search("foo".chars().collect(), 0);
+        let list = b.search(&convert_str("foo"), 0);
 
         assert!(list.contains(&"foo".chars().collect()));
         assert!(!list.contains(&"food".chars().collect()));
@@ -111,7 +800,7 @@ fn search_test() {
     }
 
     {
-        let list = b.search("foo".chars().collect(), 1);
+        let list = b.search(&convert_str("foo"), 1);
 
         assert!(list.contains(&"foo".chars().collect()));
         assert!(list.contains(&"foe".chars().collect()));
@@ -119,7 +808,7 @@ fn search_test() {
     }
 
     {
-        let list = b.search("foo".chars().collect(), 2);
+        let list = b.search(&convert_str("foo"), 2);
 
         assert!(list.contains(&"foo".chars().collect()));
         assert!(list.contains(&"fooda".chars().collect()));
@@ -130,24 +819,748 @@ fn search_test() {
     }
 
     {
-        let list = b.search("bar".chars().collect(), 1);
+        let list = b.search(&convert_str("bar"), 1);
         println!("{:?}", list);
 
         assert!(list.is_empty());
     }
 }
 
+#[test]
+fn search_with_distance_test() {
+    let mut b: BkTree<char> = BkTree::new(None);
+
+    b.add("foo".chars().collect());
+    b.add("food".chars().collect());
+    b.add("foe".chars().collect());
+    b.add("fooda".chars().collect());
+    b.add("bar".chars().collect());
+
+    let list = b.search_with_distance(&convert_str("foo"), 1);
+
+    assert_eq!(list.len(), 3);
+    assert!(list.contains(&("foo".chars().collect(), 0)));
+    assert!(list.contains(&("food".chars().collect(), 1)));
+    assert!(list.contains(&("foe".chars().collect(), 1)));
+
+    let list = b.search_with_distance(&convert_str("fod"), 2);
+
+    assert!(list.contains(&("foo".chars().collect(), 1)));
+    assert!(list.contains(&("food".chars().collect(), 1)));
+    assert!(list.contains(&("foe".chars().collect(), 1)));
+    assert!(list.contains(&("fooda".chars().collect(), 2)));
+    assert!(!list.iter().any(|(word, _)| *word == "bar".chars().collect::<Vec<char>>()));
+}
+
+#[test]
+fn nearest_test() {
+    let mut b: BkTree<char> = BkTree::new(None);
+    let list = ["foo", "food", "foodb", "foe", "fooda", "bar", "baz", "foodbar"];
+
+    b.add_list(list.iter().map(|word| word.chars().collect()).collect());
+
+    let nearest = b.nearest(&convert_str("fod"), 3);
+    let dists: Vec<usize> = nearest.iter().map(|&(_, dist)| dist).collect();
+
+    assert_eq!(dists, vec![1, 1, 1]);
+    assert!(nearest.contains(&("foo".chars().collect(), 1)));
+    assert!(nearest.contains(&("food".chars().collect(), 1)));
+    assert!(nearest.contains(&("foe".chars().collect(), 1)));
+
+    let nearest = b.nearest(&convert_str("foodba"), 2);
+
+    assert_eq!(nearest.len(), 2);
+    assert_eq!(nearest[0].1, 1);
+    assert_eq!(nearest[1].1, 1);
+    assert!(!nearest.contains(&("food".chars().collect(), 2)));
+
+    // Asking for more neighbours than there are entries returns the whole tree.
+    let nearest = b.nearest(&convert_str("bar"), 100);
+    let dists: Vec<usize> = nearest.iter().map(|&(_, dist)| dist).collect();
+    let mut sorted = dists.clone();
+    sorted.sort();
+
+    assert_eq!(nearest.len(), list.len());
+    assert_eq!(nearest[0], ("bar".chars().collect(), 0));
+    assert_eq!(dists, sorted);
+    assert_eq!(b.nearest(&convert_str("bar"), usize::MAX), nearest);
+
+    assert!(b.nearest(&convert_str("bar"), 0).is_empty());
+    assert!(BkTree::<char>::new(None).nearest(&convert_str("bar"), 3).is_empty());
+}
+
+#[test]
+fn nearest_matches_search_test() {
+    let mut b: BkTree<char> = BkTree::new(None);
+    let list = TEST_WORDS;
+
+    b.add_list(test_words());
+
+    for query in &["bok", "cak", "barks", "zzz"] {
+        let query: Vec<char> = query.chars().collect();
+        let mut expected: Vec<usize> = list.iter()
+            .map(|word| levenshtein_dist(&convert_str(word), &query))
+            .collect();
+        expected.sort();
+        expected.truncate(4);
+
+        let dists: Vec<usize> = b.nearest(&query, 4).iter().map(|&(_, dist)| dist).collect();
+        assert_eq!(dists, expected);
+    }
+}
+
+#[test]
+fn find_closest_test() {
+    let mut b: BkTree<char> = BkTree::new(None);
+    let list = TEST_WORDS;
+
+    assert_eq!(b.find_closest(&convert_str("book"), None), None);
+
+    b.add_list(test_words());
+
+    assert_eq!(b.find_closest(&convert_str("book"), None), Some(("book".chars().collect(), 0)));
+    assert_eq!(b.find_closest(&convert_str("brookx"), None), Some(("brook".chars().collect(), 1)));
+    assert_eq!(b.find_closest(&convert_str("carts"), Some(1)), Some(("cart".chars().collect(), 1)));
+    assert_eq!(b.find_closest(&convert_str("carts"), Some(0)), None);
+    assert_eq!(b.find_closest(&convert_str("xyzzyx"), Some(3)), None);
+
+    for query in &["bok", "cak", "barks", "zzz", "crate"] {
+        let query: Vec<char> = query.chars().collect();
+        let expected = list.iter()
+            .map(|word| levenshtein_dist(&convert_str(word), &query))
+            .min();
+
+        assert_eq!(b.find_closest(&query, None).map(|(_, dist)| dist), expected);
+    }
+}
+
+#[test]
+fn remove_test() {
+    let mut b: BkTree<char> = BkTree::new(None);
+    let list = ["foo", "food", "foodb", "foe", "fooda", "bar"];
+
+    b.add_list(list.iter().map(|word| word.chars().collect()).collect());
+    assert_eq!(b.len(), 6);
+
+    assert!(b.remove(&convert_str("food")));
+    assert!(!b.remove(&convert_str("food")));
+    assert!(!b.remove(&convert_str("fod")));
+    assert_eq!(b.len(), 5);
+
+    // The removed node still routes searches to its children.
+    let list = b.search(&convert_str("foo"), 2);
+
+    assert!(!list.contains(&"food".chars().collect()));
+    assert!(list.contains(&"foodb".chars().collect()));
+    assert!(list.contains(&"fooda".chars().collect()));
+    assert_eq!(b.find_closest(&convert_str("food"), None).map(|(_, dist)| dist), Some(1));
+    assert!(!b.nearest(&convert_str("food"), 10).contains(&("food".chars().collect(), 0)));
+
+    // Removing the root keeps the remaining entries reachable.
+    assert!(b.remove(&convert_str("foo")));
+    assert!(b.search(&convert_str("foo"), 0).is_empty());
+    assert!(b.search(&convert_str("foo"), 1).contains(&"foe".chars().collect()));
+
+    b.add("food".chars().collect());
+    assert_eq!(b.search(&convert_str("food"), 0), vec!["food".chars().collect::<Vec<char>>()]);
+}
+
+#[test]
+fn compact_test() {
+    let mut b: BkTree<char> = BkTree::new(None);
+
+    b.add_list(test_words());
+
+    assert!(b.remove(&convert_str("books")));
+    assert!(b.remove(&convert_str("cake")));
+    assert_eq!(b.stats().nodes, 10);
+
+    b.compact();
+    assert_eq!(b.stats().nodes, 8);
+    assert_eq!(b.len(), 8);
+
+    let list = b.search(&convert_str("cape"), 10);
+    assert_eq!(list.len(), 8);
+    assert!(!list.contains(&"books".chars().collect()));
+    assert!(!list.contains(&"cake".chars().collect()));
+
+    // Crossing the tombstone ratio compacts automatically, including a removed root.
+    for word in &["book", "boo", "boon", "cook", "cape"] {
+        assert!(b.remove(&convert_str(word)));
+    }
+
+    assert_eq!(b.len(), 3);
+    assert_eq!(b.stats().nodes, 3);
+
+    let mut list = b.search(&convert_str("bark"), 10);
+    list.sort();
+    assert_eq!(list, vec!["bark".chars().collect::<Vec<char>>(), "brook".chars().collect(), "cart".chars().collect()]);
+
+    for word in &["cart", "bark", "brook"] {
+        assert!(b.remove(&convert_str(word)));
+    }
+
+    assert!(b.is_empty());
+    assert!(b.search(&convert_str("bark"), 10).is_empty());
+}
+
+#[test]
+fn duplicate_policy_test() {
+    let foo: Vec<char> = "foo".chars().collect();
+    let food: Vec<char> = "food".chars().collect();
+
+    let mut b: BkTree<char> = BkTree::new(None);
+    assert_eq!(b.duplicate_policy(), DuplicatePolicy::KeepAll);
+    assert!(b.add(foo.clone()));
+    assert!(b.add(foo.clone()));
+    assert_eq!(b.len(), 2);
+    assert_eq!(b.search(&foo, 0).len(), 2);
+
+    let mut b: BkTree<char> = BkTree::new(None);
+    b.set_duplicate_policy(DuplicatePolicy::Ignore);
+    assert!(b.add(foo.clone()));
+    assert!(b.add(food.clone()));
+    assert!(!b.add(foo.clone()));
+    assert!(!b.add(food.clone()));
+    assert_eq!(b.len(), 2);
+    assert_eq!(b.search(&foo, 1).len(), 2);
+    assert_eq!(b.count(&food), 1);
+
+    let mut b: BkTree<char> = BkTree::new(None);
+    b.set_duplicate_policy(DuplicatePolicy::Count);
+    b.add_list(vec![foo.clone(), food.clone(), foo.clone(), foo.clone()]);
+    assert_eq!(b.len(), 2);
+    assert_eq!(b.count(&foo), 3);
+    assert_eq!(b.count(&food), 1);
+    assert_eq!(b.search(&foo, 0), vec![foo.clone()]);
+
+    assert!(b.remove(&foo));
+    assert_eq!(b.count(&foo), 2);
+    assert_eq!(b.len(), 2);
+
+    // Counts survive compaction.
+    assert!(b.remove(&food));
+    b.compact();
+    assert_eq!(b.count(&foo), 2);
+}
+
+#[test]
+fn duplicate_policy_replace_test() {
+    // Under Jaccard distance "GG" and "GGGG" are duplicates of each other.
+    let gg: Vec<char> = "GG".chars().collect();
+    let gggg: Vec<char> = "GGGG".chars().collect();
+
+    let mut b: BkTree<char> = BkTree::new(Some(jaccard_dist));
+    b.set_duplicate_policy(DuplicatePolicy::Replace);
+    assert!(b.add(gg.clone()));
+    assert!(b.add("bar".chars().collect()));
+    assert!(!b.add(gggg.clone()));
+    assert_eq!(b.len(), 2);
+    assert_eq!(b.search(&gg, 0), vec![gggg.clone()]);
+
+    // A removed entry is revived by a duplicate insert instead of growing the tree.
+    assert!(b.remove(&gggg));
+    assert!(b.add(gg.clone()));
+    assert_eq!(b.len(), 2);
+    assert_eq!(b.stats().nodes, 2);
+    assert_eq!(b.search(&gggg, 0), vec![gg.clone()]);
+}
+
+#[test]
+fn empty_word_test() {
+    let mut b: BkTree<char> = BkTree::new(None);
+
+    // The empty word is an entry like any other, not a placeholder for a missing root.
+    assert!(b.add(vec![]));
+    assert!(b.add(convert_str("foo")));
+    assert_eq!(b.len(), 2);
+    assert_eq!(b.search(&[], 0), vec![Vec::<char>::new()]);
+    assert_eq!(b.search_with_distance(&convert_str("fo"), 2), vec![(vec![], 2), (convert_str("foo"), 1)]);
+
+    assert!(b.remove(&convert_str("foo")));
+    assert!(b.remove(&[]));
+    assert!(b.is_empty());
+    assert!(b.add(convert_str("bar")));
+    assert!(b.add(vec![]));
+    assert_eq!(b.len(), 2);
+    assert_eq!(b.search(&[], 0), vec![Vec::<char>::new()]);
+}
+
+#[test]
+fn closure_metric_test() {
+    // Case-insensitive Levenshtein distance, with the folding done by the metric itself.
+    let fold = |word: &[char]| -> Vec<char> { word.iter().flat_map(|chr| chr.to_lowercase()).collect() };
+    let mut b = BkTree::with_metric(move |first: &[char], second: &[char]| levenshtein_dist(&fold(first), &fold(second)));
+
+    b.add("Foo".chars().collect());
+    b.add("BAR".chars().collect());
+
+    assert_eq!(b.search_with_distance(&convert_str("foo"), 0), vec![("Foo".chars().collect(), 0)]);
+    assert_eq!(b.find_closest(&convert_str("bat"), None), Some(("BAR".chars().collect(), 1)));
+}
+
+#[test]
+fn struct_metric_test() {
+    // Hamming distance where a mismatch costs `weight`.
+    struct Weighted {
+        weight: usize
+    }
+
+    impl Metric<u8> for Weighted {
+        fn distance(&self, first: &[u8], second: &[u8]) -> usize {
+            hamming_dist(first, second) * self.weight
+        }
+    }
+
+    let mut b: BkTree<u8, Weighted> = BkTree::with_metric(Weighted { weight: 10 });
+
+    b.add_list(vec![vec![0, 0, 0], vec![0, 0, 1], vec![1, 1, 1]]);
+
+    assert_eq!(b.metric().weight, 10);
+    assert_eq!(b.search(&[0, 0, 0], 9), vec![vec![0, 0, 0]]);
+    assert_eq!(b.search(&[0, 0, 0], 10).len(), 2);
+    assert_eq!(b.nearest(&[1, 1, 0], 1), vec![(vec![1, 1, 1], 10)]);
+}
+
+#[allow(dead_code)]
+fn same_layout<'a, T: 'a + PartialEq, N: NodeRef<'a, T>>(first: N, second: N) -> bool {
+    let mut stack = vec![(first, second)];
+
+    while let Some((first, second)) = stack.pop() {
+        if first.word() != second.word() || first.edge() != second.edge() || first.is_deleted() != second.is_deleted()
+            || first.count() != second.count() || first.child_count() != second.child_count() {
+            return false;
+        }
+
+        stack.extend((0..first.child_count()).map(|idx| (first.child(idx), second.child(idx))));
+    }
+
+    true
+}
+
+#[test]
+fn write_read_test() {
+    let mut b: BkTree<char> = BkTree::new(None);
+    b.set_duplicate_policy(DuplicatePolicy::Count);
+    b.add_list(test_words());
+    b.add(convert_str("book"));
+    b.remove(&convert_str("cake"));
+
+    let mut buf = vec![];
+    b.write_to(&mut buf).unwrap();
+
+    assert_eq!(&buf[..4], b"BKST");
+
+    let c: BkTree<char> = BkTree::read_from(&buf[..], levenshtein_dist as Dist<char>).unwrap();
+
+    assert!(same_layout(b.root().unwrap(), c.root().unwrap()));
+    assert_eq!(c.len(), b.len());
+    assert_eq!(c.duplicate_policy(), DuplicatePolicy::Count);
+    assert_eq!(c.count(&convert_str("book")), 2);
+    assert_eq!(c.search(&convert_str("cake"), 0), Vec::<Vec<char>>::new());
+    assert_eq!(c.search_with_distance(&convert_str("bok"), 1), b.search_with_distance(&convert_str("bok"), 1));
+
+    // An empty tree round-trips too.
+    let mut buf = vec![];
+    BkTree::<char>::new(None).write_to(&mut buf).unwrap();
+
+    let c: BkTree<char> = BkTree::read_from(&buf[..], levenshtein_dist as Dist<char>).unwrap();
+    assert!(c.is_empty());
+}
+
+#[test]
+fn read_invalid_test() {
+    let mut b: BkTree<u8> = BkTree::new(Some(hamming_dist));
+    b.add_list(vec![vec![0, 0], vec![0, 1], vec![1, 1]]);
+
+    let mut buf = vec![];
+    b.write_to(&mut buf).unwrap();
+
+    let read = |buf: &[u8]| BkTree::<u8>::read_from(buf, hamming_dist as Dist<u8>).err().map(|err| err.kind());
+
+    assert_eq!(read(&buf), None);
+    assert_eq!(read(&buf[..buf.len() - 1]), Some(io::ErrorKind::UnexpectedEof));
+    assert_eq!(read(b"BKSX"), Some(io::ErrorKind::InvalidData));
+
+    let mut version = buf.clone();
+    version[4] = 2;
+    assert_eq!(read(&version), Some(io::ErrorKind::InvalidData));
+
+    // The count of the root record follows the header, its edge distance and its flags.
+    let mut count = buf.clone();
+    let offset = 4 + 4 + 1 + 8 + 8 + 8 + 1;
+
+    assert_eq!(count[offset..][..8], 1u64.to_le_bytes());
+    count[offset..][..8].copy_from_slice(&0u64.to_le_bytes());
+    assert_eq!(read(&count), Some(io::ErrorKind::InvalidData));
+
+    // A huge node count in the header and a root claiming almost as many children must not allocate for them.
+    let mut huge = buf[..offset + 8 + 8 + 2].to_vec();
+    let nodes = 1u64 << 36;
+
+    huge[9..17].copy_from_slice(&nodes.to_le_bytes());
+    huge.extend_from_slice(&(nodes - 1).to_le_bytes());
+    assert_eq!(read(&huge), Some(io::ErrorKind::UnexpectedEof));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_test() {
+    use serde_json;
+
+    let mut b: BkTree<char, BuiltinMetric> = BkTree::with_metric(BuiltinMetric::Hamming);
+    let list = ["book", "cook", "boon", "bark", "cart"];
+
+    b.set_duplicate_policy(DuplicatePolicy::Ignore);
+    b.add_list(list.iter().map(convert_str).collect());
+    b.remove(&convert_str("boon"));
+
+    let json = serde_json::to_string(&b).unwrap();
+
+    assert!(json.contains("\"metric\":\"hamming\""));
+
+    let c: BkTree<char, BuiltinMetric> = serde_json::from_str(&json).unwrap();
+
+    assert!(same_layout(b.root().unwrap(), c.root().unwrap()));
+    assert_eq!(*c.metric(), BuiltinMetric::Hamming);
+    assert_eq!(c.len(), 4);
+    assert_eq!(c.duplicate_policy(), DuplicatePolicy::Ignore);
+    assert_eq!(c.search_with_distance(&convert_str("bork"), 1), b.search_with_distance(&convert_str("bork"), 1));
+
+    let unknown = json.replace("hamming", "jaro");
+    assert!(serde_json::from_str::<BkTree<char, BuiltinMetric>>(&unknown).is_err());
+
+    // Nodes are written as a flat list of records.
+    assert!(json.contains("\"records\":[{\"word\":[\"b\",\"o\",\"o\",\"k\"],\"dist\":0,\"flags\":0,\"count\":1,\"children\":"));
+    assert!(!json.contains("value"));
+
+    let tombstones = json.replace("\"tombstones\":1", "\"tombstones\":0");
+    assert!(serde_json::from_str::<BkTree<char, BuiltinMetric>>(&tombstones).is_err());
+
+    let count = json.replace("\"count\":1", "\"count\":0");
+    assert!(serde_json::from_str::<BkTree<char, BuiltinMetric>>(&count).is_err());
+
+    let empty = serde_json::to_string(&BkTree::<char, BuiltinMetric>::with_metric(BuiltinMetric::Hamming)).unwrap();
+    assert!(serde_json::from_str::<BkTree<char, BuiltinMetric>>(&empty).unwrap().is_empty());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_deep_tree_test() {
+    use serde_json;
+
+    // Under Hamming distance, one-symbol words are all 1 apart, so each insert extends a single chain.
+    let depth: u32 = 5_000;
+
+    with_small_stack(move || {
+        let mut b: BkTree<u32, BuiltinMetric> = BkTree::with_metric(BuiltinMetric::Hamming);
+
+        b.add_list((0..depth).map(|i| vec![i]).collect());
+
+        let c: BkTree<u32, BuiltinMetric> = serde_json::from_str(&serde_json::to_string(&b).unwrap()).unwrap();
+
+        assert_eq!(c.len(), depth as usize);
+        assert_eq!(c.depth(), depth as usize - 1);
+        assert_eq!(c.search(&[depth - 1], 0), vec![vec![depth - 1]]);
+    });
+}
+
+#[test]
+fn search_iter_test() {
+    let mut b: BkTree<char> = BkTree::new(None);
+    b.add_list(test_words());
+    b.remove(&convert_str("boo"));
+
+    let queries: Vec<Vec<char>> = ["bok", "cape", "book", "zzz", "bark"].iter().map(convert_str).collect();
+
+    assert_same_results(
+        |query, dist| b.search_with_distance(query, dist),
+        |query, dist| b.search_iter(query, dist).map(|(word, dist)| (word.to_vec(), dist)).collect(),
+        &queries, 0..11
+    );
+
+    assert_eq!(BkTree::<char>::new(None).search_iter(&convert_str("bok"), 5).count(), 0);
+}
+
+#[test]
+fn search_iter_lazy_test() {
+    use std::cell::Cell;
+
+    let calls = Cell::new(0);
+    let mut b = BkTree::with_metric(|first: &[char], second: &[char]| {
+        calls.set(calls.get() + 1);
+        levenshtein_dist(first, second)
+    });
+    b.add_list(test_words());
+
+    let query = convert_str("bark");
+
+    calls.set(0);
+    assert_eq!(b.search_iter(&query, 10).next(), Some((&convert_str("book")[..], 2)));
+    assert_eq!(calls.get(), 1);
+
+    calls.set(0);
+    assert_eq!(b.search_iter(&query, 10).count(), TEST_WORDS.len());
+    assert_eq!(calls.get(), TEST_WORDS.len());
+}
+
+#[test]
+fn search_with_stats_test() {
+    use std::cell::Cell;
+
+    let calls = Cell::new(0);
+    let mut b = BkTree::with_metric(|first: &[char], second: &[char]| {
+        calls.set(calls.get() + 1);
+        levenshtein_dist(first, second)
+    });
+    b.add_list(test_words());
+
+    for &(query, dist) in &[("bok", 1), ("cape", 0), ("bark", 10)] {
+        let query = convert_str(query);
+        let mut expected = b.search_with_distance(&query, dist);
+
+        calls.set(0);
+
+        let (mut found, stats) = b.search_with_stats(&query, dist);
+
+        expected.sort();
+        found.sort();
+        assert_eq!(found, expected);
+        assert_eq!(stats.distance_evaluations, calls.get());
+        assert_eq!(stats.nodes_visited, calls.get());
+        // Every node is either visited or below a pruned child.
+        assert!(stats.nodes_visited + stats.children_pruned <= TEST_WORDS.len());
+    }
+
+    let (_, stats) = b.search_with_stats(&convert_str("bark"), 10);
+
+    assert_eq!((stats.nodes_visited, stats.children_pruned), (TEST_WORDS.len(), 0));
+
+    let (_, stats) = b.search_with_stats(&convert_str("cape"), 0);
+
+    assert!(stats.children_pruned > 0);
+    assert!(stats.nodes_visited < TEST_WORDS.len());
+
+    let (found, stats) = BkTree::<char>::new(None).search_with_stats(&convert_str("bok"), 1);
+
+    assert!(found.is_empty());
+    assert_eq!(stats, SearchStats::default());
+}
+
+#[allow(dead_code)]
+fn valid_layout<'a, T: 'a, N: NodeRef<'a, T>, M: Metric<T>>(root: N, metric: &M) -> bool {
+    let mut stack = vec![root];
+
+    while let Some(node) = stack.pop() {
+        let children: Vec<N> = (0..node.child_count()).map(|idx| node.child(idx)).collect();
+
+        if children.windows(2).any(|pair| pair[0].edge() >= pair[1].edge()) {
+            return false;
+        }
+
+        if children.iter().any(|child| metric.distance(node.word(), child.word()) != child.edge()) {
+            return false;
+        }
+
+        stack.extend(children);
+    }
+
+    true
+}
+
+#[test]
+fn merge_test() {
+    let first = &TEST_WORDS[..6];
+    let second = ["book", "cape", "cart", "brook", "bark", "boon", "cook", "bake"];
+    let third = ["cart", "boo", "bookss"];
+    let mut all: BkTree<char> = BkTree::new(None);
+
+    all.add_list(first.iter().chain(second.iter()).chain(third.iter()).map(convert_str).collect());
+
+    let mut b: BkTree<char> = BkTree::new(None);
+    let mut c: BkTree<char> = BkTree::new(None);
+    let mut d: BkTree<char> = BkTree::new(None);
+
+    b.add_list(first.iter().map(convert_str).collect());
+    c.add_list(second.iter().map(convert_str).collect());
+    d.add_list(third.iter().map(convert_str).collect());
+
+    // Roots at distance 0 are grafted, the others are inserted again.
+    assert!(b.merge(c).is_ok());
+    assert!(b.merge(d).is_ok());
+    assert!(b.merge(BkTree::new(None)).is_ok());
+    assert_eq!(b.len(), all.len());
+    assert_eq!(b.stats().nodes, all.len());
+    assert!(valid_layout(b.root().unwrap(), b.metric()));
+
+    let queries: Vec<Vec<char>> = ["bok", "cake", "carts", "brookx", "book", "zzz"].iter().map(convert_str).collect();
+
+    assert_same_results(|query, dist| all.search_with_distance(query, dist), |query, dist| b.search_with_distance(query, dist), &queries, 0..4);
+
+    let mut e: BkTree<char> = BkTree::new(None);
+
+    assert!(e.merge(b.clone()).is_ok());
+    assert_eq!(e.len(), b.len());
+
+    let mut hamming: BkTree<char> = BkTree::new(Some(hamming_dist));
+
+    hamming.add(convert_str("book"));
+
+    let hamming = b.merge(hamming).unwrap_err();
+
+    assert_eq!(hamming.len(), 1);
+    assert_eq!(b.len(), all.len());
+}
+
+#[test]
+fn merge_policy_test() {
+    let mut b: BkTree<char> = BkTree::new(None);
+    let mut c: BkTree<char> = BkTree::new(None);
+
+    b.set_duplicate_policy(DuplicatePolicy::Count);
+    c.set_duplicate_policy(DuplicatePolicy::Count);
+    b.add_list(["book", "cake", "book"].iter().map(convert_str).collect());
+    c.add_list(["book", "cook", "cook", "cake"].iter().map(convert_str).collect());
+    c.remove(&convert_str("cake"));
+
+    assert!(b.merge(c).is_ok());
+    assert_eq!(b.len(), 3);
+    assert_eq!(b.count(&convert_str("book")), 3);
+    assert_eq!(b.count(&convert_str("cook")), 2);
+    assert_eq!(b.count(&convert_str("cake")), 1);
+
+    let mut d: BkTree<char> = BkTree::new(None);
+
+    d.set_duplicate_policy(DuplicatePolicy::Ignore);
+    d.add(convert_str("cook"));
+    assert!(d.merge(b).is_ok());
+    assert_eq!(d.len(), 3);
+    assert_eq!(d.count(&convert_str("book")), 1);
+    assert!(valid_layout(d.root().unwrap(), d.metric()));
+}
+
+#[test]
+fn deep_tree_test() {
+    let depth: u32 = 5_000;
+
+    with_small_stack(move || {
+        let mut b: BkTree<u32> = BkTree::new(Some(discrete));
+
+        for i in 0..depth {
+            b.add(vec![i]);
+        }
+
+        assert_eq!(b.search(&[depth - 1], 0), vec![vec![depth - 1]]);
+        assert_eq!(b.search_iter(&[depth], 1).count(), depth as usize);
+        assert_eq!(b.find_closest(&[depth - 1], None), Some((vec![depth - 1], 0)));
+        assert_eq!(b.nearest(&[depth - 1], 1), vec![(vec![depth - 1], 0)]);
+
+        let c = b.clone();
+
+        assert!(b.remove(&[depth / 2]));
+        b.compact();
+        assert_eq!(b.len(), depth as usize - 1);
+        assert_eq!(b.stats().nodes, depth as usize - 1);
+        assert!(b.search(&[depth / 2], 0).is_empty());
+        assert_eq!(c.search(&[depth / 2], 0), vec![vec![depth / 2]]);
+
+        let mut buf = vec![];
+        c.write_to(&mut buf).unwrap();
+        let d: BkTree<u32> = BkTree::read_from(&buf[..], discrete as Dist<u32>).unwrap();
+        assert_eq!(d.len(), depth as usize);
+    });
+}
+
+#[test]
+fn build_test() {
+    let mut words = vec![];
+
+    for first in ["b", "c", "f", "h", "m", "r", "s"].iter() {
+        for rest in ["at", "ats", "oat", "ook", "ooks", "art", "arts", "and", "ind", "ound"].iter() {
+            words.push(convert_str(format!("{}{}", first, rest)));
+        }
+    }
+
+    words.push(convert_str("cat"));
+
+    let mut b: BkTree<char> = BkTree::new(None);
+    let c: BkTree<char> = BkTree::build(words.clone(), None);
+
+    b.add_list(words.clone());
+
+    assert_eq!(c.len(), words.len());
+    assert_eq!(c.stats().nodes, words.len());
+
+    assert_same_results(|query, dist| b.search_with_distance(query, dist), |query, dist| c.search_with_distance(query, dist), &words, 0..3);
+
+    assert_eq!(c.search(&convert_str("cat"), 0).len(), 2);
+    assert_eq!(c.find_closest(&convert_str("bouns"), None), Some((convert_str("bound"), 1)));
+
+    let d: BkTree<char> = BkTree::build(vec![], None);
+
+    assert!(d.is_empty());
+    assert!(d.search(&convert_str("cat"), 5).is_empty());
+    assert_eq!(d.depth(), 0);
+}
+
+#[test]
+fn build_seed_test() {
+    // Words of 3 to 12 letters from a small alphabet, where sampled pivots prune better than input order.
+    let mut state: u64 = 12345;
+    let mut next = move || {
+        state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        state >> 33
+    };
+    let words: Vec<Vec<char>> = (0..1000).map(|_| {
+        let len = 3 + next() % 10;
+        (0..len).map(|_| (b'a' + (next() % 6) as u8) as char).collect()
+    }).collect();
+    let queries = &words[..100];
+
+    let mut b: BkTree<char> = BkTree::new(None);
+    let c: BkTree<char> = BkTree::build(words.clone(), None);
+    let d: BkTree<char> = BkTree::build(words.clone(), None);
+    let e = BkTree::build_with_rng(words.clone(), levenshtein_dist as Dist<char>, &mut StdRng::seed_from_u64(7));
+
+    b.add_list(words.clone());
+
+    // The same list gives the same tree unless another seed is used.
+    assert!(same_layout(c.root().unwrap(), d.root().unwrap()));
+    assert!(!same_layout(c.root().unwrap(), e.root().unwrap()));
+
+    let evaluations = |tree: &BkTree<char>| queries.iter()
+        .map(|query| tree.search_with_stats(query, 2).1.distance_evaluations)
+        .sum::<usize>();
+
+    for tree in [&c, &e].iter() {
+        assert!(evaluations(tree) < evaluations(&b), "{} evaluations against {}", evaluations(tree), evaluations(&b));
+    }
+}
+
+#[test]
+fn depth_fanout_test() {
+    let mut b: BkTree<char> = BkTree::new(None);
+
+    assert_eq!((b.depth(), b.max_fanout()), (0, 0));
+
+    b.add_list(["foo", "bar", "baz", "fob", "foobar"].iter().map(convert_str).collect());
+
+    // foo -> { fob (1), bar (3) -> { baz (1), foobar (3) } }
+    assert_eq!(b.depth(), 2);
+    assert_eq!(b.max_fanout(), 2);
+}
+
 #[test]
 fn default_dist_add_test() {
     let mut b: BkTree<char> = BkTree::new(None);
 
     let test1: Vec<char> = "foo".chars().collect();
     b.add(test1.to_owned());
-    assert_eq!(b._root.word, test1.to_owned());
+    assert_eq!(b.root().unwrap().word(), test1.to_owned());
 
     let test2: Vec<char> = "bar".chars().collect();
     b.add(test2.to_owned());
-    assert_eq!(b._root.children[0].word, test2.to_owned());
+    assert_eq!(b.root().unwrap().child(0).word(), test2.to_owned());
 }
 
 #[test]
@@ -157,12 +1570,12 @@ fn jaccard_dist_add_test() {
     let test1: Vec<char> = "foo".chars().collect();
 
     b.add(test1.to_owned());
-    assert_eq!(b._root.word, test1.to_owned());
+    assert_eq!(b.root().unwrap().word(), test1.to_owned());
 
     let test2: Vec<char> = "bar".chars().collect();
 
     b.add(test2.to_owned());
-    assert_eq!(b._root.children[0].word, test2.to_owned());
+    assert_eq!(b.root().unwrap().child(0).word(), test2.to_owned());
 }
 
 #[test]
@@ -172,12 +1585,12 @@ fn modified_jaccard_dist_add_test() {
     let test1: Vec<char> = "foo".chars().collect();
 
     b.add(test1.to_owned());
-    assert_eq!(b._root.word, test1.to_owned());
+    assert_eq!(b.root().unwrap().word(), test1.to_owned());
 
     let test2: Vec<char> = "bar".chars().collect();
 
     b.add(test2.to_owned());
-    assert_eq!(b._root.children[0].word, test2.to_owned());
+    assert_eq!(b.root().unwrap().child(0).word(), test2.to_owned());
 }
 
 #[test]
@@ -187,10 +1600,10 @@ fn hamming_dist_add_test() {
     let test1: Vec<char> = "0".chars().collect();
 
     b.add(test1.to_owned());
-    assert_eq!(b._root.word, test1.to_owned());
+    assert_eq!(b.root().unwrap().word(), test1.to_owned());
 
     let test2: Vec<char> = "f".chars().collect();
 
     b.add(test2.to_owned());
-    assert_eq!(b._root.children[0].word, test2.to_owned());
+    assert_eq!(b.root().unwrap().child(0).word(), test2.to_owned());
 }
@@ -0,0 +1,266 @@
+// Binary encoding used by `BkTree::write_to` and `BkTree::read_from`.
+//
+// All integers are little endian. A file is a header followed by one record per node in pre-order, so a tree
+// is rebuilt in a single linear pass without evaluating the metric:
+//
+//     magic        4 bytes   "BKST"
+//     version      u32       FORMAT_VERSION
+//     policy       u8        duplicate policy, see `policy_to_byte`
+//     nodes        u64       number of node records, tombstones included
+//     tombstones   u64       number of removed entries among the records
+//
+// and for each node:
+//
+//     dist         u64       edge distance to the parent, 0 for the root
+//     flags        u8        bit 0 set if the entry was removed
+//     count        u64       number of times the entry was inserted
+//     word_len     u64       number of symbols in the word
+//     word         word_len symbols, encoded by `Symbol`
+//     children     u64       number of child records that follow, in edge distance order
+//
+// The serde form of a tree holds the same records, see `NodeRecord`.
+
+use std::io::{self, Read, Write};
+
+use arena::{Arena, Child};
+use bknode::DuplicatePolicy;
+use search::NodeRef;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub const MAGIC: &[u8; 4] = b"BKST";
+pub const FORMAT_VERSION: u32 = 1;
+
+pub const FLAG_DELETED: u8 = 1;
+
+// Most children a node record makes room for up front. Counts in a file are not trusted with an allocation, longer
+// child lists grow as their records arrive.
+const RESERVED_CHILDREN: usize = 64;
+
+// A word element that can be written to and read back from the binary format.
+pub trait Symbol: Sized {
+    fn write_symbol<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+    fn read_symbol<R: Read>(reader: &mut R) -> io::Result<Self>;
+}
+
+pub fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+macro_rules! int_symbol {
+    ($($ty:ty),*) => {
+        $(
+            impl Symbol for $ty {
+                fn write_symbol<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                    writer.write_all(&self.to_le_bytes())
+                }
+
+                fn read_symbol<R: Read>(reader: &mut R) -> io::Result<$ty> {
+                    let mut buf = [0; ::std::mem::size_of::<$ty>()];
+
+                    reader.read_exact(&mut buf)?;
+                    Ok(<$ty>::from_le_bytes(buf))
+                }
+            }
+        )*
+    }
+}
+
+int_symbol!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl Symbol for char {
+    fn write_symbol<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        (*self as u32).write_symbol(writer)
+    }
+
+    fn read_symbol<R: Read>(reader: &mut R) -> io::Result<char> {
+        ::std::char::from_u32(u32::read_symbol(reader)?).ok_or_else(|| invalid_data("invalid char in word"))
+    }
+}
+
+impl Symbol for bool {
+    fn write_symbol<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        (*self as u8).write_symbol(writer)
+    }
+
+    fn read_symbol<R: Read>(reader: &mut R) -> io::Result<bool> {
+        match u8::read_symbol(reader)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid_data("invalid bool in word"))
+        }
+    }
+}
+
+pub fn write_u64<W: Write>(writer: &mut W, value: usize) -> io::Result<()> {
+    (value as u64).write_symbol(writer)
+}
+
+pub fn read_u64<R: Read>(reader: &mut R) -> io::Result<usize> {
+    let value = u64::read_symbol(reader)?;
+
+    match value > usize::MAX as u64 {
+        true => Err(invalid_data("value does not fit in usize")),
+        false => Ok(value as usize)
+    }
+}
+
+pub fn policy_to_byte(policy: DuplicatePolicy) -> u8 {
+    match policy {
+        DuplicatePolicy::Ignore => 0,
+        DuplicatePolicy::Replace => 1,
+        DuplicatePolicy::Count => 2,
+        DuplicatePolicy::KeepAll => 3
+    }
+}
+
+pub fn policy_from_byte(byte: u8) -> io::Result<DuplicatePolicy> {
+    match byte {
+        0 => Ok(DuplicatePolicy::Ignore),
+        1 => Ok(DuplicatePolicy::Replace),
+        2 => Ok(DuplicatePolicy::Count),
+        3 => Ok(DuplicatePolicy::KeepAll),
+        _ => Err(invalid_data("unknown duplicate policy"))
+    }
+}
+
+// The fields of one node record, shared by the binary format and the serde form of a tree. `word` is borrowed when
+// writing and owned when reading.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct NodeRecord<W> {
+    pub word: W,
+    pub dist: usize,
+    pub flags: u8,
+    pub count: usize,
+    pub children: usize,
+}
+
+// The records of the subtree under `root`, in pre-order.
+pub(crate) fn node_records<'a, T: 'a, N: NodeRef<'a, T>>(root: N) -> impl Iterator<Item = NodeRecord<&'a [T]>> {
+    let mut stack = vec![root];
+
+    ::std::iter::from_fn(move || {
+        let node = stack.pop()?;
+
+        // Pushed in reverse so children come out in edge distance order.
+        stack.extend((0..node.child_count()).rev().map(|idx| node.child(idx)));
+
+        Some(NodeRecord {
+            word: node.word(),
+            dist: node.edge(),
+            flags: match node.is_deleted() {
+                true => FLAG_DELETED,
+                false => 0
+            },
+            count: node.count(),
+            children: node.child_count()
+        })
+    })
+}
+
+// Rebuilds a tree from its `nodes` records in pre-order, one record at a time and without evaluating the metric.
+pub(crate) struct TreeBuilder<T> {
+    arena: Arena<T>,
+    // Nodes whose children are still being read, with the number of children left.
+    stack: Vec<(usize, usize)>,
+    nodes: usize,
+    deleted: usize,
+}
+
+impl<T> TreeBuilder<T> {
+    pub fn new(nodes: usize) -> TreeBuilder<T> {
+        TreeBuilder { arena: Arena::new(), stack: vec![], nodes, deleted: 0 }
+    }
+
+    pub fn push(&mut self, record: NodeRecord<Vec<T>>) -> Result<(), &'static str> {
+        let pushed = self.arena.len();
+
+        if pushed == self.nodes || (pushed > 0 && self.stack.is_empty()) {
+            return Err("trailing node records");
+        }
+
+        if record.count == 0 {
+            return Err("zero entry count");
+        }
+
+        if record.children > self.nodes - pushed - 1 {
+            return Err("truncated node records");
+        }
+
+        let (dist, children) = (record.dist, record.children);
+        let arena = &mut self.arena;
+        let idx = arena.push(record.word);
+
+        arena.set_count(idx, record.count);
+
+        if record.flags & FLAG_DELETED != 0 {
+            arena.set_deleted(idx, true);
+            self.deleted += 1;
+        }
+
+        if let Some(&mut (parent, ref mut left)) = self.stack.last_mut() {
+            if arena.children(parent).last().is_some_and(|last| last.dist >= dist) {
+                return Err("children out of edge distance order");
+            }
+
+            arena.push_child(parent, Child { dist, idx });
+            *left -= 1;
+        }
+
+        arena.reserve_children(idx, children.min(RESERVED_CHILDREN));
+        self.stack.push((idx, children));
+
+        // Done with every node that has all of its children.
+        while let Some(&(_, 0)) = self.stack.last() {
+            self.stack.pop();
+        }
+
+        Ok(())
+    }
+
+    // Returns the nodes once every record was pushed, `None` for a tree without nodes.
+    pub fn finish(self, tombstones: usize) -> Result<Option<Arena<T>>, &'static str> {
+        if self.arena.len() != self.nodes || !self.stack.is_empty() {
+            return Err("truncated node records");
+        }
+
+        if self.deleted != tombstones {
+            return Err("tombstone count does not match node records");
+        }
+
+        match self.nodes {
+            0 => Ok(None),
+            _ => Ok(Some(self.arena))
+        }
+    }
+}
+
+#[test]
+fn symbol_round_trip_test() {
+    let mut buf = vec![];
+
+    'a'.write_symbol(&mut buf).unwrap();
+    '\u{263a}'.write_symbol(&mut buf).unwrap();
+    0xbeefu16.write_symbol(&mut buf).unwrap();
+    (-5i64).write_symbol(&mut buf).unwrap();
+    true.write_symbol(&mut buf).unwrap();
+
+    assert_eq!(buf.len(), 4 + 4 + 2 + 8 + 1);
+
+    let mut reader = &buf[..];
+
+    assert_eq!(char::read_symbol(&mut reader).unwrap(), 'a');
+    assert_eq!(char::read_symbol(&mut reader).unwrap(), '\u{263a}');
+    assert_eq!(u16::read_symbol(&mut reader).unwrap(), 0xbeef);
+    assert_eq!(i64::read_symbol(&mut reader).unwrap(), -5);
+    assert!(bool::read_symbol(&mut reader).unwrap());
+    assert!(u8::read_symbol(&mut reader).is_err());
+}
+
+#[test]
+fn invalid_char_test() {
+    let buf = 0xd800u32.to_le_bytes();
+
+    assert_eq!(char::read_symbol(&mut &buf[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,345 @@
+// A tree that can be searched from any number of threads while words are being added.
+//
+// Nodes are shared behind `Arc`s and never modified once published. A writer copies the path from the root down to
+// the insertion point, leaving every other node shared with the previous version, then swaps in the new root.
+// Readers only hold the root lock long enough to clone an `Arc`, so a search never waits for an insert to finish.
+
+use std::mem;
+use std::sync::{Arc, Mutex, RwLock};
+
+use bktree::Dist;
+use dist::{levenshtein_dist, Metric};
+use search::{self, NodeRef, SearchIter};
+
+#[derive(Clone)]
+pub struct SharedNode<T> {
+    word: Vec<T>,
+    dist: usize,
+    children: Vec<Arc<SharedNode<T>>>,
+}
+
+// Nodes only shared with the dropped one are released with an explicit stack rather than one frame per level.
+impl<T> Drop for SharedNode<T> {
+    fn drop(&mut self) {
+        let mut stack = mem::take(&mut self.children);
+
+        while let Some(child) = stack.pop() {
+            if let Some(mut node) = Arc::into_inner(child) {
+                stack.append(&mut node.children);
+            }
+        }
+    }
+}
+
+impl<'a, T> NodeRef<'a, T> for &'a SharedNode<T> {
+    fn word(&self) -> &'a [T] {
+        &self.word
+    }
+
+    fn edge(&self) -> usize {
+        self.dist
+    }
+
+    fn is_deleted(&self) -> bool {
+        false
+    }
+
+    fn count(&self) -> usize {
+        1
+    }
+
+    fn child_count(&self) -> usize {
+        self.children.len()
+    }
+
+    fn child(&self, idx: usize) -> &'a SharedNode<T> {
+        &self.children[idx]
+    }
+}
+
+// An immutable version of a `ConcurrentBkTree`. Holding one keeps its nodes alive, so several queries can see the
+// same contents while writers move on.
+pub struct Snapshot<T, M = Dist<T>> {
+    root: Option<Arc<SharedNode<T>>>,
+    dist: Arc<M>,
+    len: usize,
+}
+
+impl<T, M> Clone for Snapshot<T, M> {
+    fn clone(&self) -> Snapshot<T, M> {
+        Snapshot {
+            root: self.root.clone(),
+            dist: self.dist.clone(),
+            len: self.len
+        }
+    }
+}
+
+impl<T: Eq + Clone, M: Metric<T>> Snapshot<T, M> {
+    // Inserts `word`, copying the nodes on its path that are still shared with other versions.
+    fn insert(&mut self, word: Vec<T>) {
+        self.len += 1;
+
+        let mut node = match self.root {
+            Some(ref mut root) => Arc::make_mut(root),
+            None => {
+                self.root = Some(Arc::new(SharedNode { word, dist: 0, children: vec![] }));
+                return;
+            }
+        };
+
+        loop {
+            let curr_dist = self.dist.distance(&node.word, &word);
+
+            match node.children.binary_search_by(|probe| probe.dist.cmp(&curr_dist)) {
+                Ok(child_idx) => node = Arc::make_mut(&mut node.children[child_idx]),
+                Err(insert_idx) => {
+                    node.children.insert(insert_idx, Arc::new(SharedNode { word, dist: curr_dist, children: vec![] }));
+                    return;
+                }
+            };
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn search(&self, word: &[T], dist: usize) -> Vec<Vec<T>> {
+        self.search_iter(word, dist).map(|(word, _)| word.to_vec()).collect()
+    }
+
+    pub fn search_with_distance(&self, word: &[T], dist: usize) -> Vec<(Vec<T>, usize)> {
+        self.search_iter(word, dist).map(|(word, dist)| (word.to_vec(), dist)).collect()
+    }
+
+    pub fn search_iter<'a>(&'a self, word: &'a [T], dist: usize) -> impl Iterator<Item = (&'a [T], usize)> + 'a {
+        let nodes = self.root.as_ref().map(|root| SearchIter::new(&**root, word, dist, &*self.dist));
+
+        nodes.into_iter().flatten().map(|(node, dist)| (&node.word[..], dist))
+    }
+
+    pub fn find_closest(&self, word: &[T], max_dist: Option<usize>) -> Option<(Vec<T>, usize)> {
+        let root = self.root.as_ref()?;
+
+        search::find_closest(&**root, word, max_dist.unwrap_or(usize::MAX), &*self.dist)
+            .map(|(node, dist)| (node.word.to_owned(), dist))
+    }
+
+    pub fn nearest(&self, word: &[T], k: usize) -> Vec<(Vec<T>, usize)> {
+        match self.root {
+            Some(ref root) => search::nearest(&**root, word, k, &*self.dist).into_iter()
+                .map(|(node, dist)| (node.word.to_owned(), dist))
+                .collect(),
+            None => vec![]
+        }
+    }
+}
+
+// Duplicates are kept as with `DuplicatePolicy::KeepAll`.
+pub struct _ConcurrentBkTree<T, M = Dist<T>> {
+    current: RwLock<Snapshot<T, M>>,
+    // Serialises writers, so each one builds on the version published by the previous one.
+    writer: Mutex<()>,
+}
+
+impl<T: Eq + Clone> _ConcurrentBkTree<T> {
+    pub fn new(func: Option<Dist<T>>) -> _ConcurrentBkTree<T> {
+        match func {
+            Some(func) => _ConcurrentBkTree::with_metric(func),
+            None => _ConcurrentBkTree::with_metric(levenshtein_dist)
+        }
+    }
+}
+
+impl<T: Eq + Clone, M: Metric<T>> _ConcurrentBkTree<T, M> {
+    pub fn with_metric(metric: M) -> _ConcurrentBkTree<T, M> {
+        _ConcurrentBkTree {
+            current: RwLock::new(Snapshot { root: None, dist: Arc::new(metric), len: 0 }),
+            writer: Mutex::new(())
+        }
+    }
+
+    // The latest published version of the tree.
+    pub fn snapshot(&self) -> Snapshot<T, M> {
+        self.current.read().unwrap().clone()
+    }
+
+    pub fn add(&self, word: Vec<T>) {
+        self.add_list(vec![word]);
+    }
+
+    // Inserts every word of `list`, publishing them together once all are in.
+    pub fn add_list(&self, list: Vec<Vec<T>>) {
+        let _writer = self.writer.lock().unwrap();
+        let mut next = self.snapshot();
+
+        for word in list {
+            next.insert(word);
+        }
+
+        *self.current.write().unwrap() = next;
+    }
+
+    pub fn len(&self) -> usize {
+        self.current.read().unwrap().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn search(&self, word: &[T], dist: usize) -> Vec<Vec<T>> {
+        self.snapshot().search(word, dist)
+    }
+
+    pub fn search_with_distance(&self, word: &[T], dist: usize) -> Vec<(Vec<T>, usize)> {
+        self.snapshot().search_with_distance(word, dist)
+    }
+
+    pub fn find_closest(&self, word: &[T], max_dist: Option<usize>) -> Option<(Vec<T>, usize)> {
+        self.snapshot().find_closest(word, max_dist)
+    }
+
+    pub fn nearest(&self, word: &[T], k: usize) -> Vec<(Vec<T>, usize)> {
+        self.snapshot().nearest(word, k)
+    }
+}
+
+pub type ConcurrentBkTree<T, M = Dist<T>> = _ConcurrentBkTree<T, M>;
+
+#[allow(dead_code)]
+fn hex_word(i: u32) -> Vec<char> {
+    format!("{:x}", i.wrapping_mul(7919)).chars().collect()
+}
+
+#[test]
+fn concurrent_matches_tree_test() {
+    use bktree::BkTree;
+
+    let mut b: BkTree<char> = BkTree::new(None);
+    let c: ConcurrentBkTree<char> = ConcurrentBkTree::new(None);
+
+    for i in 0..500 {
+        b.add(hex_word(i));
+        c.add(hex_word(i));
+    }
+
+    c.add_list(vec![hex_word(3), hex_word(501)]);
+    b.add_list(vec![hex_word(3), hex_word(501)]);
+
+    assert_eq!(c.len(), 502);
+
+    for i in 0..20 {
+        let query = hex_word(i * 37);
+
+        for dist in 0..3 {
+            assert_eq!(c.search_with_distance(&query, dist), b.search_with_distance(&query, dist));
+        }
+
+        assert_eq!(c.find_closest(&query, None).map(|(_, dist)| dist), b.find_closest(&query, None).map(|(_, dist)| dist));
+        assert_eq!(
+            c.nearest(&query, 5).iter().map(|&(_, dist)| dist).collect::<Vec<usize>>(),
+            b.nearest(&query, 5).iter().map(|&(_, dist)| dist).collect::<Vec<usize>>()
+        );
+    }
+
+    let empty: ConcurrentBkTree<char> = ConcurrentBkTree::new(None);
+
+    assert!(empty.is_empty());
+    assert!(empty.search(&hex_word(1), 3).is_empty());
+    assert_eq!(empty.find_closest(&hex_word(1), None), None);
+}
+
+#[test]
+fn concurrent_snapshot_test() {
+    let c: ConcurrentBkTree<char> = ConcurrentBkTree::new(None);
+
+    c.add(hex_word(1));
+
+    let before = c.snapshot();
+
+    c.add(hex_word(2));
+
+    assert_eq!(before.len(), 1);
+    assert!(before.search(&hex_word(2), 0).is_empty());
+    assert_eq!(c.search(&hex_word(2), 0), vec![hex_word(2)]);
+}
+
+#[test]
+fn concurrent_add_search_test() {
+    use std::thread;
+
+    let words: u32 = 600;
+    let c: ConcurrentBkTree<char> = ConcurrentBkTree::new(None);
+
+    thread::scope(|scope| {
+        // Two writers adding disjoint halves of the words, in order.
+        for writer in 0..2 {
+            let c = &c;
+
+            scope.spawn(move || {
+                for i in (writer..words).step_by(2) {
+                    c.add(hex_word(i));
+                }
+            });
+        }
+
+        for _ in 0..4 {
+            let c = &c;
+
+            scope.spawn(move || {
+                let mut last_len = 0;
+
+                while last_len < words as usize {
+                    let snapshot = c.snapshot();
+                    let len = snapshot.len();
+
+                    assert!(len >= last_len);
+
+                    // Each writer adds its words in order, so a version holds a prefix of both halves.
+                    let found: Vec<u32> = (0..words).filter(|&i| !snapshot.search(&hex_word(i), 0).is_empty()).collect();
+
+                    assert_eq!(found.len(), len);
+
+                    for writer in 0..2 {
+                        let half: Vec<u32> = found.iter().cloned().filter(|i| i % 2 == writer).collect();
+
+                        assert!(half.iter().enumerate().all(|(idx, &i)| i == writer + 2 * idx as u32));
+                    }
+
+                    last_len = len;
+                }
+            });
+        }
+    });
+
+    assert_eq!(c.len(), words as usize);
+}
+
+#[test]
+fn concurrent_deep_tree_test() {
+    use dist::{discrete, with_small_stack};
+
+    let depth: u32 = 5_000;
+
+    with_small_stack(move || {
+        let c: ConcurrentBkTree<u32> = ConcurrentBkTree::new(Some(discrete));
+        let first = c.snapshot();
+
+        c.add_list((0..depth / 2).map(|i| vec![i]).collect());
+
+        let half = c.snapshot();
+
+        c.add_list((depth / 2..depth).map(|i| vec![i]).collect());
+
+        assert!(first.is_empty());
+        assert_eq!(half.search(&[depth - 1], 0), Vec::<Vec<u32>>::new());
+        assert_eq!(c.search(&[depth - 1], 0), vec![vec![depth - 1]]);
+        assert_eq!(c.nearest(&[depth - 1], 1), vec![(vec![depth - 1], 0)]);
+    });
+}
@@ -6,7 +6,75 @@ use std::cmp::{min, max};
 
 const MAX_PERCENT_DIST: usize = 1_000_000;
 
-fn run_func<T>(func: &Fn(Vec<T>, Vec<T>) -> usize, first: Vec<T>, second: Vec<T>, minimum: usize, maximum: usize) -> usize {
+// A distance between two words. Any `fn(&[T], &[T]) -> usize` or closure is a metric, so the functions below
+// can be used as is, while structs implementing it can carry their own parameters.
+pub trait Metric<T> {
+    fn distance(&self, first: &[T], second: &[T]) -> usize;
+}
+
+impl<T, F> Metric<T> for F where F: Fn(&[T], &[T]) -> usize {
+    fn distance(&self, first: &[T], second: &[T]) -> usize {
+        self(first, second)
+    }
+}
+
+// A metric that can be referred to by name, so a tree using it can be saved and restored.
+// Function pointers have no stable identity, custom metrics register a name by implementing this trait.
+pub trait NamedMetric<T>: Metric<T> + Sized {
+    fn name(&self) -> &str;
+    fn from_name(name: &str) -> Option<Self>;
+}
+
+// The distance functions of this module, as a metric that knows its own name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuiltinMetric {
+    Levenshtein,
+    Hamming,
+    Jaccard,
+    ModifiedJaccard,
+}
+
+impl BuiltinMetric {
+    pub fn all() -> [BuiltinMetric; 4] {
+        [BuiltinMetric::Levenshtein, BuiltinMetric::Hamming, BuiltinMetric::Jaccard, BuiltinMetric::ModifiedJaccard]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            BuiltinMetric::Levenshtein => "levenshtein",
+            BuiltinMetric::Hamming => "hamming",
+            BuiltinMetric::Jaccard => "jaccard",
+            BuiltinMetric::ModifiedJaccard => "modified_jaccard"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<BuiltinMetric> {
+        BuiltinMetric::all().iter().cloned().find(|metric| metric.name() == name)
+    }
+}
+
+impl<T: Eq + Hash> Metric<T> for BuiltinMetric {
+    fn distance(&self, first: &[T], second: &[T]) -> usize {
+        match *self {
+            BuiltinMetric::Levenshtein => levenshtein_dist(first, second),
+            BuiltinMetric::Hamming => hamming_dist(first, second),
+            BuiltinMetric::Jaccard => jaccard_dist(first, second),
+            BuiltinMetric::ModifiedJaccard => modified_jaccard_dist(first, second)
+        }
+    }
+}
+
+impl<T: Eq + Hash> NamedMetric<T> for BuiltinMetric {
+    fn name(&self) -> &str {
+        BuiltinMetric::name(self)
+    }
+
+    fn from_name(name: &str) -> Option<BuiltinMetric> {
+        BuiltinMetric::from_name(name)
+    }
+}
+
+fn run_func<T>(func: &dyn Fn(&[T], &[T]) -> usize, first: &[T], second: &[T], minimum: usize, maximum: usize) -> usize {
     let len1 = first.len();
     let len2 = second.len();
 
@@ -18,13 +86,13 @@ fn run_func<T>(func: &Fn(Vec<T>, Vec<T>) -> usize, first: Vec<T>, second: Vec<T>
         return maximum;
     }
 
-    return func(first, second);
+    func(first, second)
 }
 
-pub fn jaccard_dist<T>(first: Vec<T>, second: Vec<T>) -> usize where T: Eq + Hash {
-    let func = |fir: Vec<T>, sec: Vec<T>| -> usize {
-        let set1: HashSet<T> = fir.into_iter().collect();
-        let set2: HashSet<T> = sec.into_iter().collect();
+pub fn jaccard_dist<T>(first: &[T], second: &[T]) -> usize where T: Eq + Hash {
+    let func = |fir: &[T], sec: &[T]| -> usize {
+        let set1: HashSet<&T> = fir.iter().collect();
+        let set2: HashSet<&T> = sec.iter().collect();
 
         let intersect_set = set1.intersection(&set2);
         let union_set = set1.union(&set2);
@@ -36,39 +104,35 @@ pub fn jaccard_dist<T>(first: Vec<T>, second: Vec<T>) -> usize where T: Eq + Has
 
     };
 
-    return run_func(&func, first, second, 0, MAX_PERCENT_DIST);
+    run_func(&func, first, second, 0, MAX_PERCENT_DIST)
 }
 
-pub fn modified_jaccard_dist<T: Eq>(first: Vec<T>, second: Vec<T>) -> usize where Vec<T>: Clone {
-    let func = |fir: Vec<T>, sec: Vec<T>| -> usize {
-        let len1 = fir.len();
-        let mut sec_copy = sec.clone();
+pub fn modified_jaccard_dist<T: Eq>(first: &[T], second: &[T]) -> usize {
+    let func = |fir: &[T], sec: &[T]| -> usize {
+        // Each element of the second word can only be matched once.
+        let mut used = vec![false; sec.len()];
 
         let mut intersect = 0;
-        let mut union = len1 + sec_copy.len();
+        let mut union = fir.len() + sec.len();
 
-        for i in 0..len1 {
-            let len2 = sec_copy.len();
-            for j in 0..len2 {
-                if fir[i] == sec_copy[j] {
+        for item in fir.iter() {
+            for (j, other) in sec.iter().enumerate() {
+                if !used[j] && item == other {
                     intersect += 1;
                     union -= 1;
-
-                    // Swap remove doesn't preseve ordering, but computes in O(1) time.
-                    sec_copy.swap_remove(j);
+                    used[j] = true;
                     break;
                 }
             }
-
         }
 
-        return MAX_PERCENT_DIST - (MAX_PERCENT_DIST * intersect) / union;
+        MAX_PERCENT_DIST - (MAX_PERCENT_DIST * intersect) / union
     };
 
-    return run_func(&func, first, second, 0, MAX_PERCENT_DIST);
+    run_func(&func, first, second, 0, MAX_PERCENT_DIST)
 }
 
-pub fn levenshtein_dist<T: Eq>(first: Vec<T>, second: Vec<T>) -> usize {
+pub fn levenshtein_dist<T: Eq>(first: &[T], second: &[T]) -> usize {
     let first_len: usize = first.len();
     let second_len: usize = second.len();
 
@@ -82,12 +146,12 @@ pub fn levenshtein_dist<T: Eq>(first: Vec<T>, second: Vec<T>) -> usize {
 
     let mut dist = vec![vec![0; second_len + 1]; first_len + 1];
 
-    for i in 0..first_len + 1 {
-        dist[i][0] = i as usize;
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
     }
 
-    for i in 0..second_len + 1 {
-        dist[0][i] = i as usize;
+    for (i, cell) in dist[0].iter_mut().enumerate() {
+        *cell = i;
     }
 
     for i in 1..first_len + 1 {
@@ -102,11 +166,11 @@ pub fn levenshtein_dist<T: Eq>(first: Vec<T>, second: Vec<T>) -> usize {
         }
     }
 
-    return dist[first_len][second_len];
+    dist[first_len][second_len]
 }
 
-pub fn hamming_dist<T: Eq>(first: Vec<T>, second: Vec<T>) -> usize {
-    let func = |fir: Vec<T>, sec: Vec<T>| -> usize {
+pub fn hamming_dist<T: Eq>(first: &[T], second: &[T]) -> usize {
+    let func = |fir: &[T], sec: &[T]| -> usize {
         let len1 = fir.len();
         let len2 = sec.len();
 
@@ -116,29 +180,29 @@ pub fn hamming_dist<T: Eq>(first: Vec<T>, second: Vec<T>) -> usize {
 
         let mut dist: usize = 0;
 
-        for i in 0..len1 {
-            if fir[i] != sec[i] {
+        for (a, b) in fir.iter().zip(sec.iter()) {
+            if a != b {
                 dist += 1;
             }
         }
 
-        return dist;
+        dist
     };
 
     let len1 = first.len();
     let len2 = second.len();
 
-    return run_func(&func, first, second, 0, max(len1, len2));
+    run_func(&func, first, second, 0, max(len1, len2))
 }
 
 #[allow(dead_code)]
-fn function<T: Eq + Hash>(_a: Vec<T>, _b: Vec<T>) -> usize {
-    return 0;
+fn function<T: Eq + Hash>(_a: &[T], _b: &[T]) -> usize {
+    0
 }
 
 #[allow(dead_code)]
-fn convert_str<T: ToString>(word: T) -> Vec<char> {
-    return word.to_string().chars().collect();
+pub(crate) fn convert_str<T: ToString>(word: T) -> Vec<char> {
+    word.to_string().chars().collect()
 }
 
 #[allow(dead_code)]
@@ -161,15 +225,65 @@ fn convert_hex(word: String) -> Vec<u32> {
     };
 
     for chr in word.chars() {
-        match hex_char(chr) {
-            Some(hex_chr) => {
-                ret.extend(to_bin(hex_chr));
-            },
-            None => {}
+        if let Some(hex_chr) = hex_char(chr) {
+            ret.extend(to_bin(hex_chr));
         }
     }
 
-    return ret;
+    ret
+}
+
+// Word list shared by the tests of the tree and its variants.
+#[cfg(test)]
+pub(crate) const TEST_WORDS: [&str; 10] = ["book", "books", "cake", "boo", "boon", "cook", "cape", "cart", "brook", "bark"];
+
+#[cfg(test)]
+pub(crate) fn test_words() -> Vec<Vec<char>> {
+    TEST_WORDS.iter().map(convert_str).collect()
+}
+
+// Checks that two searches find the same entries, in any order, for every query and distance.
+#[cfg(test)]
+pub(crate) fn assert_same_results<A, B>(first: A, second: B, queries: &[Vec<char>], dists: ::std::ops::Range<usize>)
+    where A: Fn(&[char], usize) -> Vec<(Vec<char>, usize)>, B: Fn(&[char], usize) -> Vec<(Vec<char>, usize)> {
+    for query in queries {
+        for dist in dists.clone() {
+            let mut expected = first(query, dist);
+            let mut found = second(query, dist);
+
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected, "query {:?} within {}", query, dist);
+        }
+    }
+}
+
+// Under the discrete metric every word is at distance 1 from every other, so each insert extends a single chain.
+#[cfg(test)]
+pub(crate) fn discrete(first: &[u32], second: &[u32]) -> usize {
+    match first == second {
+        true => 0,
+        false => 1
+    }
+}
+
+// Runs `test` on a thread with far too small a stack for one frame per level of a deep tree.
+#[cfg(test)]
+pub(crate) fn with_small_stack<F: FnOnce() + Send + 'static>(test: F) {
+    ::std::thread::Builder::new().stack_size(64 * 1024).spawn(test).unwrap().join().unwrap();
+}
+
+#[test]
+fn builtin_metric_test() {
+    for metric in BuiltinMetric::all().iter() {
+        assert_eq!(BuiltinMetric::from_name(metric.name()), Some(*metric));
+    }
+
+    assert_eq!(BuiltinMetric::from_name("jaro"), None);
+    assert_eq!(BuiltinMetric::Levenshtein.distance(&convert_str("foo"), &convert_str("food")), 1);
+    assert_eq!(BuiltinMetric::Hamming.distance(&convert_str("foo"), &convert_str("foe")), 1);
+    assert_eq!(BuiltinMetric::Jaccard.distance(&convert_str("bar"), &convert_str("baz")), 500_000);
+    assert_eq!(BuiltinMetric::ModifiedJaccard.distance(&convert_str("GG"), &convert_str("GGGG")), 500_000);
 }
 
 #[test]
@@ -190,85 +304,85 @@ fn len_check_test() {
     let v2: Vec<String> = vec![];
     let v_full: Vec<String> = vec!["foo".to_string()];
 
-    assert_eq!(run_func(&function, v1.clone(), v2.clone(), 0, 1), 0);
-    assert_eq!(run_func(&function, v_full.clone(), v2.clone(), 0, 1), 1);
-    assert_eq!(run_func(&function, v1.clone(), v_full.clone(), 0, 1), 1);
+    assert_eq!(run_func(&function, &v1, &v2, 0, 1), 0);
+    assert_eq!(run_func(&function, &v_full, &v2, 0, 1), 1);
+    assert_eq!(run_func(&function, &v1, &v_full, 0, 1), 1);
 }
 
 #[test]
 fn levenshtein_dist_test_samples() {
-    assert_eq!(levenshtein_dist(convert_str("foo"), convert_str("food")), 1);
-    assert_eq!(levenshtein_dist(convert_str("foo"), convert_str("bar")), 3);
-    assert_eq!(levenshtein_dist(convert_str("foo"), convert_str("foe")), 1);
+    assert_eq!(levenshtein_dist(&convert_str("foo"), &convert_str("food")), 1);
+    assert_eq!(levenshtein_dist(&convert_str("foo"), &convert_str("bar")), 3);
+    assert_eq!(levenshtein_dist(&convert_str("foo"), &convert_str("foe")), 1);
 }
 
 #[test]
 fn levenshtein_dist_test_unicode() {
-    assert_eq!(levenshtein_dist(convert_str("johndoe1"), convert_str("johndoe\u{263a}")), 1);
-    assert_eq!(levenshtein_dist(convert_str("johndoe1"), convert_str("johndoe\u{263a}1")), 1);
-    assert_eq!(levenshtein_dist(convert_str("johndoe1"), convert_str("johndoe\u{263a}\u{263a}")), 2);
-    assert_eq!(levenshtein_dist(convert_str("johndoe\u{263a}"), convert_str("johndoe1")), 1);
+    assert_eq!(levenshtein_dist(&convert_str("johndoe1"), &convert_str("johndoe\u{263a}")), 1);
+    assert_eq!(levenshtein_dist(&convert_str("johndoe1"), &convert_str("johndoe\u{263a}1")), 1);
+    assert_eq!(levenshtein_dist(&convert_str("johndoe1"), &convert_str("johndoe\u{263a}\u{263a}")), 2);
+    assert_eq!(levenshtein_dist(&convert_str("johndoe\u{263a}"), &convert_str("johndoe1")), 1);
 }
 
 #[test]
 fn hamming_dist_test() {
-    assert_eq!(hamming_dist(convert_str("foo"), convert_str("bar")), 3);
-    assert_eq!(hamming_dist(convert_str(""), convert_str("")), 0);
-    assert_eq!(hamming_dist(convert_str("foo"), convert_str("")), 3);
-    assert_eq!(hamming_dist(convert_str(""), convert_str("bar")), 3);
-    assert_eq!(hamming_dist(convert_str("foo"), convert_str("foe")), 1);
+    assert_eq!(hamming_dist(&convert_str("foo"), &convert_str("bar")), 3);
+    assert_eq!(hamming_dist(&convert_str(""), &convert_str("")), 0);
+    assert_eq!(hamming_dist(&convert_str("foo"), &convert_str("")), 3);
+    assert_eq!(hamming_dist(&convert_str(""), &convert_str("bar")), 3);
+    assert_eq!(hamming_dist(&convert_str("foo"), &convert_str("foe")), 1);
 }
 
 #[test]
 fn jaccard_dist_test() {
-    assert_eq!(jaccard_dist(convert_str("foo"), convert_str("bar")), 1_000_000);
-    assert_eq!(jaccard_dist(convert_str("bar"), convert_str("ba")), 333_334);
-    assert_eq!(jaccard_dist(convert_str("bar"), convert_str("baz")), 500_000);
-    assert_eq!(jaccard_dist(convert_str("GG"), convert_str("GGGG")), 0);
-    assert_eq!(jaccard_dist(convert_str("GGGG"), convert_str("GG")), 0);
-    assert_eq!(jaccard_dist(convert_str("fooba 1234"), convert_str("fooba1234")), 111_112);
+    assert_eq!(jaccard_dist(&convert_str("foo"), &convert_str("bar")), 1_000_000);
+    assert_eq!(jaccard_dist(&convert_str("bar"), &convert_str("ba")), 333_334);
+    assert_eq!(jaccard_dist(&convert_str("bar"), &convert_str("baz")), 500_000);
+    assert_eq!(jaccard_dist(&convert_str("GG"), &convert_str("GGGG")), 0);
+    assert_eq!(jaccard_dist(&convert_str("GGGG"), &convert_str("GG")), 0);
+    assert_eq!(jaccard_dist(&convert_str("fooba 1234"), &convert_str("fooba1234")), 111_112);
 }
 
 #[test]
 fn modified_jaccard_dist_test() {
-    assert_eq!(modified_jaccard_dist(convert_str("foo"), convert_str("bar")), 1_000_000);
-    assert_eq!(modified_jaccard_dist(convert_str("bar"), convert_str("ba")), 333_334);
-    assert_eq!(modified_jaccard_dist(convert_str("bar"), convert_str("baz")), 500_000);
-    assert_eq!(modified_jaccard_dist(convert_str("GG"), convert_str("GGGG")), 500_000);
-    assert_eq!(modified_jaccard_dist(convert_str("GGGG"), convert_str("GG")), 500_000);
-    assert_eq!(modified_jaccard_dist(convert_str("fooba 1234"), convert_str("fooba1234")), 100_000);
+    assert_eq!(modified_jaccard_dist(&convert_str("foo"), &convert_str("bar")), 1_000_000);
+    assert_eq!(modified_jaccard_dist(&convert_str("bar"), &convert_str("ba")), 333_334);
+    assert_eq!(modified_jaccard_dist(&convert_str("bar"), &convert_str("baz")), 500_000);
+    assert_eq!(modified_jaccard_dist(&convert_str("GG"), &convert_str("GGGG")), 500_000);
+    assert_eq!(modified_jaccard_dist(&convert_str("GGGG"), &convert_str("GG")), 500_000);
+    assert_eq!(modified_jaccard_dist(&convert_str("fooba 1234"), &convert_str("fooba1234")), 100_000);
 }
 
 #[test]
 fn hamming_dist_test_edges() {
-    assert_eq!(hamming_dist(convert_hex("".to_string()), convert_hex("".to_string())), 0);
-    assert_eq!(hamming_dist(convert_hex("".to_string()), convert_hex("a".to_string())), 8);
-    assert_eq!(hamming_dist(convert_hex("a".to_string()), convert_hex("".to_string())), 8);
-    assert_eq!(hamming_dist(convert_hex("aa".to_string()), convert_hex("a".to_string())), 16);
+    assert_eq!(hamming_dist(&convert_hex("".to_string()), &convert_hex("".to_string())), 0);
+    assert_eq!(hamming_dist(&convert_hex("".to_string()), &convert_hex("a".to_string())), 8);
+    assert_eq!(hamming_dist(&convert_hex("a".to_string()), &convert_hex("".to_string())), 8);
+    assert_eq!(hamming_dist(&convert_hex("aa".to_string()), &convert_hex("a".to_string())), 16);
 }
 
 #[test]
 fn hamming_dist_test_sample() {
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("435e9db1634baca2".to_string())), 36);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("e13c832b7ce2720f".to_string())), 30);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("cd87c969b794125a".to_string())), 28);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("096d864c93b396b7".to_string())), 32);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("6dc3693d11d0da4b".to_string())), 20);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("4f6ad94847cd2539".to_string())), 34);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("33edac42c731b135".to_string())), 34);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("9327939737447c1c".to_string())), 34);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("5fa9e49021de9176".to_string())), 36);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("a991569a1a66ed99".to_string())), 30);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("eb90ed295a62b465".to_string())), 30);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("4d67581a3f97283c".to_string())), 36);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("87506bfe01a4f84f".to_string())), 14);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("9fade410215e517e".to_string())), 34);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("d7bbcb6c3a369040".to_string())), 28);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("435e9db1634baca2".to_string())), 36);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("e13c832b7ce2720f".to_string())), 30);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("cd87c969b794125a".to_string())), 28);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("096d864c93b396b7".to_string())), 32);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("6dc3693d11d0da4b".to_string())), 20);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("4f6ad94847cd2539".to_string())), 34);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("33edac42c731b135".to_string())), 34);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("9327939737447c1c".to_string())), 34);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("5fa9e49021de9176".to_string())), 36);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("a991569a1a66ed99".to_string())), 30);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("eb90ed295a62b465".to_string())), 30);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("4d67581a3f97283c".to_string())), 36);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("87506bfe01a4f84f".to_string())), 14);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("9fade410215e517e".to_string())), 34);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("d7bbcb6c3a369040".to_string())), 28);
 }
 
 #[test]
 fn hamming_dist_test_capital() {
-    assert_eq!(hamming_dist(convert_hex("0590EB7E1129FA5B".to_string()), convert_hex("D7BBCB6C3A369040".to_string())), 28);
-    assert_eq!(hamming_dist(convert_hex("0590EB7E1129FA5B".to_string()), convert_hex("d7bbcb6c3a369040".to_string())), 28);
-    assert_eq!(hamming_dist(convert_hex("0590eb7e1129fa5b".to_string()), convert_hex("D7BBCB6C3A369040".to_string())), 28);
+    assert_eq!(hamming_dist(&convert_hex("0590EB7E1129FA5B".to_string()), &convert_hex("D7BBCB6C3A369040".to_string())), 28);
+    assert_eq!(hamming_dist(&convert_hex("0590EB7E1129FA5B".to_string()), &convert_hex("d7bbcb6c3a369040".to_string())), 28);
+    assert_eq!(hamming_dist(&convert_hex("0590eb7e1129fa5b".to_string()), &convert_hex("D7BBCB6C3A369040".to_string())), 28);
 }
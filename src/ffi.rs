@@ -0,0 +1,191 @@
+// C interface over trees of Unicode strings, declared in include/bkstring.h.
+//
+// Words cross the boundary as NUL-terminated UTF-8 and are stored as `char`s, so distances count code points.
+// The header is generated by cbindgen from this file, which is why items here carry `///` comments: they end up in it.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use bktree::BkTree;
+use dist::BuiltinMetric;
+
+/// A tree of UTF-8 words. Create it with `bk_tree_new` or `bk_tree_new_with_metric` and release it with
+/// `bk_tree_free`.
+pub struct BkTreeHandle {
+    tree: BkTree<char, BuiltinMetric>,
+}
+
+/// A word found by `bk_tree_search`, with its distance from the query.
+#[repr(C)]
+pub struct BkMatch {
+    pub word: *mut c_char,
+    pub distance: usize,
+}
+
+/// The matches of one search, owned by the caller until passed to `bk_matches_free`.
+#[repr(C)]
+pub struct BkMatches {
+    pub matches: *mut BkMatch,
+    pub len: usize,
+}
+
+unsafe fn read_word(word: *const c_char) -> Option<Vec<char>> {
+    if word.is_null() {
+        return None;
+    }
+
+    CStr::from_ptr(word).to_str().ok().map(|word| word.chars().collect())
+}
+
+/// Creates an empty tree using the Levenshtein distance.
+#[no_mangle]
+pub extern "C" fn bk_tree_new() -> *mut BkTreeHandle {
+    Box::into_raw(Box::new(BkTreeHandle { tree: BkTree::with_metric(BuiltinMetric::Levenshtein) }))
+}
+
+/// Creates an empty tree using the built-in metric called `name`: "levenshtein", "hamming", "jaccard" or
+/// "modified_jaccard". Returns NULL for any other name.
+///
+/// # Safety
+///
+/// `name` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn bk_tree_new_with_metric(name: *const c_char) -> *mut BkTreeHandle {
+    if name.is_null() {
+        return ptr::null_mut();
+    }
+
+    let metric = CStr::from_ptr(name).to_str().ok().and_then(BuiltinMetric::from_name);
+
+    match metric {
+        Some(metric) => Box::into_raw(Box::new(BkTreeHandle { tree: BkTree::with_metric(metric) })),
+        None => ptr::null_mut()
+    }
+}
+
+/// Releases a tree. Does nothing if `tree` is NULL.
+///
+/// # Safety
+///
+/// `tree` must be NULL or a tree returned by this library that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn bk_tree_free(tree: *mut BkTreeHandle) {
+    if !tree.is_null() {
+        drop(Box::from_raw(tree));
+    }
+}
+
+/// Adds a UTF-8 word. Returns 1 if a new entry was created, 0 if the word was merged into an existing one and -1
+/// if `tree` or `word` is NULL or `word` is not valid UTF-8.
+///
+/// # Safety
+///
+/// `tree` must be NULL or a live tree, and `word` NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn bk_tree_add_utf8(tree: *mut BkTreeHandle, word: *const c_char) -> c_int {
+    let word = match (tree.is_null(), read_word(word)) {
+        (false, Some(word)) => word,
+        _ => return -1
+    };
+
+    match (*tree).tree.add(word) {
+        true => 1,
+        false => 0
+    }
+}
+
+/// Number of live entries in the tree, 0 if `tree` is NULL.
+///
+/// # Safety
+///
+/// `tree` must be NULL or a live tree.
+#[no_mangle]
+pub unsafe extern "C" fn bk_tree_len(tree: *const BkTreeHandle) -> usize {
+    match tree.is_null() {
+        true => 0,
+        false => (*tree).tree.len()
+    }
+}
+
+/// Finds every entry within `max_dist` of the UTF-8 `word`. Returns NULL if `tree` or `word` is NULL or `word` is
+/// not valid UTF-8, otherwise matches that must be released with `bk_matches_free`.
+///
+/// # Safety
+///
+/// `tree` must be NULL or a live tree, and `word` NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn bk_tree_search(tree: *const BkTreeHandle, word: *const c_char, max_dist: usize) -> *mut BkMatches {
+    let word = match (tree.is_null(), read_word(word)) {
+        (false, Some(word)) => word,
+        _ => return ptr::null_mut()
+    };
+
+    let matches: Box<[BkMatch]> = (*tree).tree.search_iter(&word, max_dist).map(|(word, distance)| {
+        // Words only come from C strings, so they never hold a NUL.
+        let word = CString::new(word.iter().collect::<String>()).unwrap();
+
+        BkMatch { word: word.into_raw(), distance }
+    }).collect();
+
+    let len = matches.len();
+
+    Box::into_raw(Box::new(BkMatches { matches: Box::into_raw(matches) as *mut BkMatch, len }))
+}
+
+/// Releases the matches returned by `bk_tree_search`. Does nothing if `matches` is NULL.
+///
+/// # Safety
+///
+/// `matches` must be NULL or a result of `bk_tree_search` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn bk_matches_free(matches: *mut BkMatches) {
+    if matches.is_null() {
+        return;
+    }
+
+    let matches = Box::from_raw(matches);
+    let list = Box::from_raw(ptr::slice_from_raw_parts_mut(matches.matches, matches.len));
+
+    for found in list.iter() {
+        drop(CString::from_raw(found.word));
+    }
+}
+
+#[test]
+fn ffi_test() {
+    unsafe {
+        let tree = bk_tree_new();
+
+        for word in ["book\0", "books\0", "cake\0", "b\u{f6}ok\0", "book\0"].iter() {
+            assert_eq!(bk_tree_add_utf8(tree, word.as_ptr() as *const c_char), 1);
+        }
+
+        assert_eq!(bk_tree_add_utf8(tree, b"\xff\0".as_ptr() as *const c_char), -1);
+        assert_eq!(bk_tree_add_utf8(tree, ptr::null()), -1);
+        assert_eq!(bk_tree_len(tree), 5);
+
+        let matches = bk_tree_search(tree, b"bok\0".as_ptr() as *const c_char, 1);
+        let mut found: Vec<(String, usize)> = (0..(*matches).len).map(|idx| {
+            let found = &*(*matches).matches.add(idx);
+
+            (CStr::from_ptr(found.word).to_str().unwrap().to_string(), found.distance)
+        }).collect();
+
+        found.sort();
+        assert_eq!(found, vec![("book".to_string(), 1), ("book".to_string(), 1), ("b\u{f6}ok".to_string(), 1)]);
+
+        bk_matches_free(matches);
+        bk_matches_free(ptr::null_mut());
+        assert!(bk_tree_search(tree, ptr::null(), 1).is_null());
+        bk_tree_free(tree);
+        bk_tree_free(ptr::null_mut());
+
+        assert!(bk_tree_new_with_metric(b"cosine\0".as_ptr() as *const c_char).is_null());
+
+        let tree = bk_tree_new_with_metric(b"hamming\0".as_ptr() as *const c_char);
+
+        assert!(!tree.is_null());
+        bk_tree_free(tree);
+    }
+}
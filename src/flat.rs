@@ -0,0 +1,204 @@
+// A read-only copy of a `BkTree` with its arena packed: nodes are laid out breadth first without any room left by
+// earlier changes, so the children of a node sit next to each other and a search walks contiguous memory. Build it
+// with `BkTree::to_flat` once the tree is complete.
+
+use arena::{Arena, ArenaRef};
+use bktree::{Dist, _BkTree};
+use dist::Metric;
+use search::{self, NodeRef, SearchIter, SearchStats};
+
+#[derive(Clone)]
+pub struct _FlatBkTree<T, M = Dist<T>> {
+    arena: Arena<T>,
+    dist: M,
+    len: usize
+}
+
+impl<T: Eq + Clone + Sized, M: Metric<T> + Clone> _BkTree<T, M> {
+    // Copies the tree into the flat layout, nodes are laid out breadth first.
+    pub fn to_flat(&self) -> _FlatBkTree<T, M> {
+        _FlatBkTree {
+            arena: self.arena().packed(),
+            dist: self.metric().clone(),
+            len: self.len()
+        }
+    }
+}
+
+impl<T: Eq + Clone + Sized, M: Metric<T>> _FlatBkTree<T, M> {
+    pub(crate) fn root(&self) -> Option<ArenaRef<'_, T>> {
+        self.arena.root()
+    }
+
+    pub fn metric(&self) -> &M {
+        &self.dist
+    }
+
+    // Number of live entries in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Bytes allocated for nodes, words and child lists.
+    pub fn heap_size(&self) -> usize {
+        self.arena.heap_size()
+    }
+
+    pub fn search(&self, word: &[T], dist: usize) -> Vec<Vec<T>> {
+        self.search_iter(word, dist).map(|(word, _)| word.to_vec()).collect()
+    }
+
+    pub fn search_with_distance(&self, word: &[T], dist: usize) -> Vec<(Vec<T>, usize)> {
+        self.search_iter(word, dist).map(|(word, dist)| (word.to_vec(), dist)).collect()
+    }
+
+    pub fn search_with_stats(&self, word: &[T], dist: usize) -> (Vec<(Vec<T>, usize)>, SearchStats) {
+        match self.root() {
+            Some(root) => {
+                let (nodes, stats) = search::search_with_stats(root, word, dist, &self.dist);
+
+                (nodes.into_iter().map(|(node, dist)| (node.word().to_vec(), dist)).collect(), stats)
+            },
+            None => (vec![], Default::default())
+        }
+    }
+
+    pub fn search_iter<'a>(&'a self, word: &'a [T], dist: usize) -> impl Iterator<Item = (&'a [T], usize)> + 'a {
+        let nodes = self.root().map(|root| SearchIter::new(root, word, dist, &self.dist));
+
+        nodes.into_iter().flatten().map(|(node, dist)| (node.word(), dist))
+    }
+
+    pub fn find_closest(&self, word: &[T], max_dist: Option<usize>) -> Option<(Vec<T>, usize)> {
+        let root = self.root()?;
+
+        search::find_closest(root, word, max_dist.unwrap_or(usize::MAX), &self.dist)
+            .map(|(node, dist)| (node.word().to_vec(), dist))
+    }
+
+    pub fn nearest(&self, word: &[T], k: usize) -> Vec<(Vec<T>, usize)> {
+        match self.root() {
+            Some(root) => search::nearest(root, word, k, &self.dist).into_iter()
+                .map(|(node, dist)| (node.word().to_vec(), dist))
+                .collect(),
+            None => vec![]
+        }
+    }
+
+    // Number of times `word` was inserted, which only exceeds 1 under `DuplicatePolicy::Count`.
+    pub fn count(&self, word: &[T]) -> usize {
+        let mut node = match self.root() {
+            Some(root) => root,
+            None => return 0
+        };
+
+        loop {
+            let curr_dist = self.dist.distance(node.word(), word);
+
+            if curr_dist == 0 && !node.is_deleted() && node.word() == word {
+                return node.count();
+            }
+
+            let (min_idx, max_idx) = search::child_window(node, curr_dist, 0);
+
+            match min_idx < max_idx {
+                true => node = node.child(min_idx),
+                false => return 0
+            };
+        }
+    }
+}
+
+pub type FlatBkTree<T, M = Dist<T>> = _FlatBkTree<T, M>;
+
+// Bytes allocated for the same words kept with one allocation per node, as `BkMap` does.
+#[allow(dead_code)]
+fn boxed_heap_size(words: &[Vec<u8>]) -> usize {
+    use std::mem;
+
+    use bknode::BkNode;
+    use dist::levenshtein_dist;
+
+    let mut root = BkNode::new(words[0].clone(), (), 0);
+
+    for word in &words[1..] {
+        root.insert(word.clone(), (), &levenshtein_dist);
+    }
+
+    let mut size = 0;
+    let mut stack = vec![&root];
+
+    while let Some(node) = stack.pop() {
+        size += node.word.capacity() + node.children.capacity() * mem::size_of::<BkNode<u8>>();
+        stack.extend(node.children.iter());
+    }
+
+    size
+}
+
+#[test]
+fn flat_matches_tree_test() {
+    use bknode::DuplicatePolicy;
+    use bktree::BkTree;
+    use dist::{assert_same_results, convert_str, test_words};
+
+    let mut b: BkTree<char> = BkTree::new(None);
+
+    b.set_duplicate_policy(DuplicatePolicy::Count);
+    b.add_list(test_words());
+    b.add(convert_str("book"));
+    b.remove(&convert_str("cake"));
+
+    let f = b.to_flat();
+
+    assert_eq!(f.len(), b.len());
+    assert_eq!(f.count(&convert_str("book")), 2);
+    assert_eq!(f.count(&convert_str("cake")), 0);
+
+    let queries: Vec<Vec<char>> = ["bok", "cake", "carts", "brookx", "zzz"].iter().map(convert_str).collect();
+
+    assert_same_results(|query, dist| b.search_with_distance(query, dist), |query, dist| f.search_with_distance(query, dist), &queries, 0..4);
+
+    for query in queries {
+        for dist in 0..4 {
+            let (_, tree_stats) = b.search_with_stats(&query, dist);
+            let (_, flat_stats) = f.search_with_stats(&query, dist);
+
+            assert_eq!(flat_stats.nodes_visited, tree_stats.nodes_visited);
+            assert_eq!(flat_stats.children_pruned, tree_stats.children_pruned);
+        }
+
+        assert_eq!(f.find_closest(&query, None).map(|(_, dist)| dist), b.find_closest(&query, None).map(|(_, dist)| dist));
+        assert_eq!(
+            f.nearest(&query, 4).iter().map(|&(_, dist)| dist).collect::<Vec<usize>>(),
+            b.nearest(&query, 4).iter().map(|&(_, dist)| dist).collect::<Vec<usize>>()
+        );
+    }
+
+    let empty = BkTree::<char>::new(None).to_flat();
+
+    assert!(empty.is_empty());
+    assert!(empty.search(&convert_str("bok"), 3).is_empty());
+    assert_eq!(empty.find_closest(&convert_str("bok"), None), None);
+}
+
+#[test]
+fn flat_heap_size_test() {
+    use bktree::BkTree;
+
+    let words: Vec<Vec<u8>> = (0..2000u32).map(|i| format!("{:x}", i * 7919).into_bytes()).collect();
+    let mut b: BkTree<u8> = BkTree::new(None);
+
+    b.add_list(words.clone());
+
+    let f = b.to_flat();
+    let boxed = boxed_heap_size(&words);
+
+    assert_eq!(f.len(), 2000);
+    assert!(b.heap_size() < boxed);
+    assert!(f.heap_size() < b.heap_size());
+}
@@ -0,0 +1,263 @@
+// Similarity self-join and threshold clustering over the entries of a `BkTree`.
+//
+// Nodes are numbered in breadth-first order, where every node comes after its parent. The nodes numbered below any
+// given node therefore form a subtree hanging from the root, and searching only that subtree for each node finds
+// every pair exactly once, from its later member, without rebuilding anything.
+
+use bktree::_BkTree;
+use dist::Metric;
+use search::{child_window, NodeRef};
+
+// Every node of the tree in breadth-first order, with the number of its first child. The children of a node are
+// numbered consecutively, in edge distance order.
+pub(crate) fn breadth_first<'a, T: 'a, N: NodeRef<'a, T>>(root: N) -> Vec<(N, usize)> {
+    let mut nodes = vec![(root, 0)];
+    let mut idx = 0;
+
+    while idx < nodes.len() {
+        let node = nodes[idx].0;
+
+        nodes[idx].1 = nodes.len();
+        nodes.extend((0..node.child_count()).map(|idx| (node.child(idx), 0)));
+        idx += 1;
+    }
+
+    nodes
+}
+
+// Returns the live nodes numbered below `idx` within `max_dist` of it, with their distance, in increasing order.
+pub(crate) fn partners<'a, T: 'a, N: NodeRef<'a, T>, M: Metric<T>>(nodes: &[(N, usize)], idx: usize, max_dist: usize, metric: &M) -> Vec<(usize, usize)> {
+    let word = nodes[idx].0.word();
+    let mut found = vec![];
+    let mut stack = match idx {
+        0 => vec![],
+        _ => vec![0]
+    };
+
+    while let Some(curr) = stack.pop() {
+        let (node, first_child) = nodes[curr];
+        let curr_dist = metric.distance(node.word(), word);
+        let (min_idx, max_idx) = child_window(node, curr_dist, max_dist);
+
+        if curr_dist <= max_dist && !node.is_deleted() {
+            found.push((curr, curr_dist));
+        }
+
+        // A child numbered after `idx` heads a subtree that is numbered after it too.
+        stack.extend((first_child + min_idx..first_child + max_idx).take_while(|&child| child < idx));
+    }
+
+    found.sort_unstable();
+    found
+}
+
+// Union-find over node numbers, with union by size and path halving.
+struct DisjointSets {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(len: usize) -> DisjointSets {
+        DisjointSets {
+            parent: (0..len).collect(),
+            size: vec![1; len]
+        }
+    }
+
+    fn find(&mut self, mut idx: usize) -> usize {
+        while self.parent[idx] != idx {
+            self.parent[idx] = self.parent[self.parent[idx]];
+            idx = self.parent[idx];
+        }
+
+        idx
+    }
+
+    fn union(&mut self, first: usize, second: usize) {
+        let (first, second) = (self.find(first), self.find(second));
+
+        if first == second {
+            return;
+        }
+
+        let (large, small) = match self.size[first] < self.size[second] {
+            true => (second, first),
+            false => (first, second)
+        };
+
+        self.parent[small] = large;
+        self.size[large] += self.size[small];
+    }
+}
+
+impl<T: Eq + Clone + Sized, M: Metric<T>> _BkTree<T, M> {
+    // Returns every unordered pair of entries within `dist` of each other, once, with their distance.
+    // Entries inserted several times under `DuplicatePolicy::KeepAll` pair with each other at distance 0.
+    pub fn pairs_within(&self, dist: usize) -> Vec<(Vec<T>, Vec<T>, usize)> {
+        let nodes = match self.root() {
+            Some(root) => breadth_first(root),
+            None => return vec![]
+        };
+
+        let mut pairs = vec![];
+
+        for (idx, &(node, _)) in nodes.iter().enumerate() {
+            if node.is_deleted() {
+                continue;
+            }
+
+            pairs.extend(partners(&nodes, idx, dist, self.metric()).into_iter()
+                .map(|(other, dist)| (nodes[other].0.word().to_owned(), node.word().to_owned(), dist)));
+        }
+
+        pairs
+    }
+
+    // Groups the entries into single-linkage clusters: two entries share a cluster when a chain of entries, each
+    // within `dist` of the next, links them. Returns every entry with its cluster id, ids being numbered from 0 in
+    // order of first appearance.
+    pub fn cluster(&self, dist: usize) -> Vec<(Vec<T>, usize)> {
+        let nodes = match self.root() {
+            Some(root) => breadth_first(root),
+            None => return vec![]
+        };
+
+        let mut sets = DisjointSets::new(nodes.len());
+
+        for (idx, &(node, _)) in nodes.iter().enumerate() {
+            if node.is_deleted() {
+                continue;
+            }
+
+            for (other, _) in partners(&nodes, idx, dist, self.metric()) {
+                sets.union(idx, other);
+            }
+        }
+
+        // Cluster id of each set representative, assigned as they are first met.
+        let mut ids = vec![None; nodes.len()];
+        let mut clusters = 0;
+        let mut entries = vec![];
+
+        for (idx, &(node, _)) in nodes.iter().enumerate() {
+            if node.is_deleted() {
+                continue;
+            }
+
+            let root = sets.find(idx);
+            let id = *ids[root].get_or_insert_with(|| {
+                clusters += 1;
+                clusters - 1
+            });
+
+            entries.push((node.word().to_owned(), id));
+        }
+
+        entries
+    }
+}
+
+#[allow(dead_code)]
+fn brute_force_pairs(words: &[Vec<char>], dist: usize) -> Vec<(Vec<char>, Vec<char>, usize)> {
+    use dist::levenshtein_dist;
+
+    let mut pairs = vec![];
+
+    for (idx, first) in words.iter().enumerate() {
+        for second in words[idx + 1..].iter() {
+            let curr_dist = levenshtein_dist(first, second);
+
+            if curr_dist <= dist {
+                pairs.push((first.clone().min(second.clone()), first.clone().max(second.clone()), curr_dist));
+            }
+        }
+    }
+
+    pairs.sort();
+    pairs
+}
+
+#[test]
+fn pairs_within_test() {
+    use bktree::BkTree;
+    use dist::{convert_str, test_words};
+
+    let mut words = test_words();
+    let mut b: BkTree<char> = BkTree::new(None);
+
+    words.extend(["book", "cook", "gone"].iter().map(convert_str));
+    b.add_list(words.clone());
+
+    for dist in 0..5 {
+        let mut pairs: Vec<(Vec<char>, Vec<char>, usize)> = b.pairs_within(dist).into_iter()
+            .map(|(first, second, dist)| (first.clone().min(second.clone()), first.max(second), dist))
+            .collect();
+
+        pairs.sort();
+        assert_eq!(pairs, brute_force_pairs(&words, dist));
+    }
+
+    assert_eq!(b.pairs_within(0), vec![(convert_str("book"), convert_str("book"), 0), (convert_str("cook"), convert_str("cook"), 0)]);
+
+    b.remove(&convert_str("cake"));
+
+    let words: Vec<Vec<char>> = words.into_iter().filter(|word| *word != convert_str("cake")).collect();
+    let mut pairs: Vec<(Vec<char>, Vec<char>, usize)> = b.pairs_within(2).into_iter()
+        .map(|(first, second, dist)| (first.clone().min(second.clone()), first.max(second), dist))
+        .collect();
+
+    pairs.sort();
+    assert_eq!(pairs, brute_force_pairs(&words, 2));
+    assert!(BkTree::<char>::new(None).pairs_within(3).is_empty());
+}
+
+#[test]
+fn cluster_test() {
+    use std::collections::HashMap;
+
+    use bktree::BkTree;
+    use dist::convert_str;
+
+    let list = ["book", "books", "boon", "cake", "cape", "tape", "zebra", "book", "brook"];
+    let mut b: BkTree<char> = BkTree::new(None);
+
+    b.add_list(list.iter().map(convert_str).collect());
+
+    let clusters = b.cluster(1);
+    let ids: HashMap<String, usize> = clusters.iter().map(|(word, id)| (word.iter().collect(), *id)).collect();
+
+    assert_eq!(clusters.len(), list.len());
+    assert_eq!(clusters[0], (convert_str("book"), 0));
+    // books, boon and brook are each 1 away from book.
+    assert_eq!(ids["books"], ids["book"]);
+    assert_eq!(ids["boon"], ids["book"]);
+    assert_eq!(ids["brook"], ids["book"]);
+    // cake - cape - tape is a chain, cake and tape are 2 apart.
+    assert_eq!(ids["cake"], ids["tape"]);
+    assert_ne!(ids["cake"], ids["book"]);
+    assert_ne!(ids["zebra"], ids["book"]);
+    assert_ne!(ids["zebra"], ids["cake"]);
+
+    let mut distinct: Vec<usize> = clusters.iter().map(|&(_, id)| id).collect();
+
+    distinct.sort();
+    distinct.dedup();
+    assert_eq!(distinct, vec![0, 1, 2]);
+
+    // Clusters match the connected components of `pairs_within`.
+    let pairs = b.pairs_within(1);
+
+    assert!(pairs.iter().all(|(first, second, _)| ids[&first.iter().collect::<String>()] == ids[&second.iter().collect::<String>()]));
+
+    // Only the two copies of book are at distance 0.
+    assert_eq!(b.cluster(0).iter().map(|&(_, id)| id).max(), Some(7));
+
+    b.remove(&convert_str("cape"));
+
+    let ids: HashMap<String, usize> = b.cluster(1).into_iter().map(|(word, id)| (word.iter().collect(), id)).collect();
+
+    assert_ne!(ids["cake"], ids["tape"]);
+    assert!(!ids.contains_key("cape"));
+    assert!(BkTree::<char>::new(None).cluster(2).is_empty());
+}
@@ -1,3 +1,29 @@
+extern crate rand;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "python")]
+extern crate pyo3;
+// The pyo3 macros refer to `::core`, which a 2015 edition crate only resolves once it is declared.
+#[cfg(feature = "python")]
+extern crate core;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+
+pub mod arena;
 pub mod bktree;
 pub mod bknode;
 pub mod dist;
+pub mod bkmap;
+pub mod codec;
+pub mod search;
+pub mod flat;
+pub mod stats;
+pub mod concurrent;
+pub mod join;
+pub mod ffi;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "rayon")]
+pub mod parallel;
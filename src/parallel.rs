@@ -0,0 +1,138 @@
+// Parallel queries over trees that are no longer modified, enabled by the `rayon` feature.
+
+use rayon::prelude::*;
+
+use bktree::_BkTree;
+use dist::Metric;
+use flat::_FlatBkTree;
+use join::{breadth_first, partners};
+use search::{child_window, NodeRef};
+
+// Collects every live node of the subtree within `max_dist` of `word`. The tree is walked one level at a time, with
+// the nodes of a level handled in parallel, so the work is spread out whatever the shape of the tree.
+pub fn par_search<'a, T, N, M>(root: N, word: &[T], max_dist: usize, metric: &M) -> Vec<(N, usize)>
+    where T: 'a + Sync, N: NodeRef<'a, T> + Send + Sync, M: Metric<T> + Sync {
+    let mut results = vec![];
+    let mut level = vec![root];
+
+    while !level.is_empty() {
+        let visited: Vec<_> = level.par_iter().map(|&node| {
+            let curr_dist = metric.distance(node.word(), word);
+            let (min_idx, max_idx) = child_window(node, curr_dist, max_dist);
+            let hit = match curr_dist <= max_dist && !node.is_deleted() {
+                true => Some((node, curr_dist)),
+                false => None
+            };
+
+            (hit, (min_idx..max_idx).map(|idx| node.child(idx)).collect::<Vec<N>>())
+        }).collect();
+
+        level = vec![];
+
+        for (hit, children) in visited {
+            results.extend(hit);
+            level.extend(children);
+        }
+    }
+
+    results
+}
+
+impl<T: Eq + Clone + Sized + Send + Sync, M: Metric<T> + Sync> _BkTree<T, M> {
+    // Runs `search_with_distance` for every query on the rayon thread pool, returning the results in query order.
+    pub fn search_batch(&self, queries: &[Vec<T>], dist: usize) -> Vec<Vec<(Vec<T>, usize)>> {
+        queries.par_iter().map(|query| self.search_with_distance(query, dist)).collect()
+    }
+
+    // Same as `search_with_distance`, but the descent of this single query is spread over the rayon thread pool.
+    // Only worth it for wide searches of large trees.
+    pub fn par_search_with_distance(&self, word: &[T], dist: usize) -> Vec<(Vec<T>, usize)> {
+        match self.root() {
+            Some(root) => par_search(root, word, dist, self.metric()).into_iter()
+                .map(|(node, dist)| (node.word().to_owned(), dist))
+                .collect(),
+            None => vec![]
+        }
+    }
+
+    // Same as `pairs_within`, with the partners of each entry searched on the rayon thread pool.
+    pub fn par_pairs_within(&self, dist: usize) -> Vec<(Vec<T>, Vec<T>, usize)> {
+        let nodes = match self.root() {
+            Some(root) => breadth_first(root),
+            None => return vec![]
+        };
+
+        let pairs: Vec<Vec<(usize, usize, usize)>> = (0..nodes.len()).into_par_iter()
+            .filter(|&idx| !nodes[idx].0.is_deleted())
+            .map(|idx| partners(&nodes, idx, dist, self.metric()).into_iter().map(|(other, dist)| (other, idx, dist)).collect())
+            .collect();
+
+        pairs.into_iter().flatten()
+            .map(|(first, second, dist)| (nodes[first].0.word().to_owned(), nodes[second].0.word().to_owned(), dist))
+            .collect()
+    }
+}
+
+impl<T: Eq + Clone + Sized + Send + Sync, M: Metric<T> + Sync> _FlatBkTree<T, M> {
+    pub fn search_batch(&self, queries: &[Vec<T>], dist: usize) -> Vec<Vec<(Vec<T>, usize)>> {
+        queries.par_iter().map(|query| self.search_with_distance(query, dist)).collect()
+    }
+
+    pub fn par_search_with_distance(&self, word: &[T], dist: usize) -> Vec<(Vec<T>, usize)> {
+        match self.root() {
+            Some(root) => par_search(root, word, dist, self.metric()).into_iter()
+                .map(|(node, dist)| (node.word().to_vec(), dist))
+                .collect(),
+            None => vec![]
+        }
+    }
+}
+
+#[test]
+fn search_batch_test() {
+    use bktree::BkTree;
+    use dist::{assert_same_results, convert_str, test_words};
+
+    let mut b: BkTree<char> = BkTree::new(None);
+
+    b.add_list(test_words());
+    b.remove(&convert_str("boon"));
+
+    let f = b.to_flat();
+    let queries: Vec<Vec<char>> = ["bok", "cape", "zzz", "bark", "carts", "boon"].iter().map(convert_str).collect();
+
+    for dist in 0..4 {
+        let expected: Vec<Vec<(Vec<char>, usize)>> = queries.iter().map(|query| b.search_with_distance(query, dist)).collect();
+
+        assert_eq!(b.search_batch(&queries, dist), expected);
+        assert_eq!(f.search_batch(&queries, dist), expected);
+    }
+
+    assert_same_results(|query, dist| b.search_with_distance(query, dist), |query, dist| b.par_search_with_distance(query, dist), &queries, 0..4);
+    assert_same_results(|query, dist| b.search_with_distance(query, dist), |query, dist| f.par_search_with_distance(query, dist), &queries, 0..4);
+
+    let empty: BkTree<char> = BkTree::new(None);
+
+    assert_eq!(empty.search_batch(&queries, 2), vec![vec![]; queries.len()]);
+    assert!(empty.par_search_with_distance(&queries[0], 2).is_empty());
+    assert!(b.search_batch(&[], 2).is_empty());
+}
+
+#[test]
+fn par_pairs_within_test() {
+    use bktree::BkTree;
+
+    let mut b: BkTree<u8> = BkTree::new(None);
+
+    for i in 0..300u32 {
+        b.add(format!("{:x}", i * 7919).into_bytes());
+    }
+
+    b.remove(&format!("{:x}", 7919).into_bytes());
+
+    for dist in 0..3 {
+        assert_eq!(b.par_pairs_within(dist), b.pairs_within(dist));
+    }
+
+    assert!(BkTree::<u8>::new(None).par_pairs_within(2).is_empty());
+}
@@ -0,0 +1,213 @@
+// Python bindings, enabled by the `python` feature and loaded as the `bkstring` extension module.
+// A tree holds either `str` or `bytes` words, chosen when it is created. `str` words are compared by code point.
+
+use std::io;
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use bktree::BkTree;
+use dist::BuiltinMetric;
+
+#[derive(FromPyObject, IntoPyObject)]
+enum Word {
+    Str(String),
+    Bytes(Vec<u8>),
+}
+
+enum Words {
+    Str(BkTree<char, BuiltinMetric>),
+    Bytes(BkTree<u8, BuiltinMetric>),
+}
+
+#[pyclass(name = "BkTree", module = "bkstring")]
+pub struct PyBkTree {
+    words: Words,
+}
+
+// Runs `$body` with the tree, the query converted to the symbols it holds and `$wrap`, which turns words found
+// back into Python objects. Fails with a `TypeError` if the query is not of the kind of words the tree holds.
+macro_rules! with_tree {
+    ($words:expr, $word:expr, |$tree:ident, $query:ident, $wrap:ident| $body:expr) => {
+        match ($words, $word) {
+            (Words::Str($tree), Word::Str(word)) => {
+                let $query: Vec<char> = word.chars().collect();
+                let $wrap = |word: &[char]| Word::Str(word.iter().collect());
+
+                Ok($body)
+            },
+            (Words::Bytes($tree), Word::Bytes($query)) => {
+                let $wrap = |word: &[u8]| Word::Bytes(word.to_vec());
+
+                Ok($body)
+            },
+            (Words::Str(_), _) => Err(PyTypeError::new_err("this tree holds str words")),
+            (Words::Bytes(_), _) => Err(PyTypeError::new_err("this tree holds bytes words"))
+        }
+    }
+}
+
+// Accepts the names of `BuiltinMetric` as well as those of the distance functions, e.g. "levenshtein_dist".
+fn metric_from_name(name: &str) -> PyResult<BuiltinMetric> {
+    BuiltinMetric::from_name(name.strip_suffix("_dist").unwrap_or(name))
+        .ok_or_else(|| PyValueError::new_err(format!("unknown metric: {}", name)))
+}
+
+fn invalid_state(err: io::Error) -> PyErr {
+    PyValueError::new_err(format!("invalid tree state: {}", err))
+}
+
+#[pymethods]
+impl PyBkTree {
+    #[new]
+    #[pyo3(signature = (metric = "levenshtein", binary = false))]
+    fn new(metric: &str, binary: bool) -> PyResult<PyBkTree> {
+        let metric = metric_from_name(metric)?;
+        let words = match binary {
+            true => Words::Bytes(BkTree::with_metric(metric)),
+            false => Words::Str(BkTree::with_metric(metric))
+        };
+
+        Ok(PyBkTree { words })
+    }
+
+    #[getter]
+    fn metric(&self) -> &'static str {
+        match self.words {
+            Words::Str(ref tree) => tree.metric().name(),
+            Words::Bytes(ref tree) => tree.metric().name()
+        }
+    }
+
+    // Whether the tree holds `bytes` rather than `str` words.
+    #[getter]
+    fn binary(&self) -> bool {
+        match self.words {
+            Words::Str(_) => false,
+            Words::Bytes(_) => true
+        }
+    }
+
+    fn add(&mut self, word: Word) -> PyResult<bool> {
+        with_tree!(&mut self.words, word, |tree, query, _wrap| tree.add(query))
+    }
+
+    fn add_list(&mut self, words: Vec<Word>) -> PyResult<()> {
+        for word in words {
+            self.add(word)?;
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, word: Word) -> PyResult<bool> {
+        with_tree!(&mut self.words, word, |tree, query, _wrap| tree.remove(&query))
+    }
+
+    fn __len__(&self) -> usize {
+        match self.words {
+            Words::Str(ref tree) => tree.len(),
+            Words::Bytes(ref tree) => tree.len()
+        }
+    }
+
+    fn __contains__(&self, word: Word) -> PyResult<bool> {
+        with_tree!(&self.words, word, |tree, query, _wrap| tree.count(&query) > 0)
+    }
+
+    // Every entry within `max_dist` of `word`, with its distance.
+    fn search(&self, word: Word, max_dist: usize) -> PyResult<Vec<(Word, usize)>> {
+        with_tree!(&self.words, word, |tree, query, wrap| {
+            tree.search_iter(&query, max_dist).map(|(word, dist)| (wrap(word), dist)).collect()
+        })
+    }
+
+    #[pyo3(signature = (word, max_dist = None))]
+    fn find_closest(&self, word: Word, max_dist: Option<usize>) -> PyResult<Option<(Word, usize)>> {
+        with_tree!(&self.words, word, |tree, query, wrap| {
+            tree.find_closest(&query, max_dist).map(|(word, dist)| (wrap(&word), dist))
+        })
+    }
+
+    fn nearest(&self, word: Word, k: usize) -> PyResult<Vec<(Word, usize)>> {
+        with_tree!(&self.words, word, |tree, query, wrap| {
+            tree.nearest(&query, k).into_iter().map(|(word, dist)| (wrap(&word), dist)).collect()
+        })
+    }
+
+    // Pickling goes through the binary format of `BkTree::write_to`.
+    fn __getnewargs__(&self) -> (&'static str, bool) {
+        (self.metric(), self.binary())
+    }
+
+    fn __getstate__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let mut state = vec![];
+
+        match self.words {
+            Words::Str(ref tree) => tree.write_to(&mut state),
+            Words::Bytes(ref tree) => tree.write_to(&mut state)
+        }?;
+
+        Ok(PyBytes::new(py, &state))
+    }
+
+    fn __setstate__(&mut self, state: Vec<u8>) -> PyResult<()> {
+        match self.words {
+            Words::Str(ref mut tree) => *tree = BkTree::read_from(&state[..], *tree.metric()).map_err(invalid_state)?,
+            Words::Bytes(ref mut tree) => *tree = BkTree::read_from(&state[..], *tree.metric()).map_err(invalid_state)?
+        };
+
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("BkTree(metric='{}', binary={}, len={})", self.metric(), if self.binary() { "True" } else { "False" }, self.__len__())
+    }
+}
+
+fn distance(metric: BuiltinMetric, first: Word, second: Word) -> PyResult<usize> {
+    use dist::Metric;
+
+    match (first, second) {
+        (Word::Str(first), Word::Str(second)) => {
+            let first: Vec<char> = first.chars().collect();
+            let second: Vec<char> = second.chars().collect();
+
+            Ok(metric.distance(&first, &second))
+        },
+        (Word::Bytes(first), Word::Bytes(second)) => Ok(metric.distance(&first, &second)),
+        _ => Err(PyTypeError::new_err("both words must be str or both bytes"))
+    }
+}
+
+#[pyfunction]
+fn levenshtein_dist(first: Word, second: Word) -> PyResult<usize> {
+    distance(BuiltinMetric::Levenshtein, first, second)
+}
+
+#[pyfunction]
+fn hamming_dist(first: Word, second: Word) -> PyResult<usize> {
+    distance(BuiltinMetric::Hamming, first, second)
+}
+
+#[pyfunction]
+fn jaccard_dist(first: Word, second: Word) -> PyResult<usize> {
+    distance(BuiltinMetric::Jaccard, first, second)
+}
+
+#[pyfunction]
+fn modified_jaccard_dist(first: Word, second: Word) -> PyResult<usize> {
+    distance(BuiltinMetric::ModifiedJaccard, first, second)
+}
+
+#[pymodule]
+fn bkstring(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyBkTree>()?;
+    module.add_function(wrap_pyfunction!(self::levenshtein_dist, module)?)?;
+    module.add_function(wrap_pyfunction!(self::hamming_dist, module)?)?;
+    module.add_function(wrap_pyfunction!(self::jaccard_dist, module)?)?;
+    module.add_function(wrap_pyfunction!(self::modified_jaccard_dist, module)?)?;
+
+    Ok(())
+}
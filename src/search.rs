@@ -0,0 +1,280 @@
+// Query algorithms shared by every tree layout. A layout only has to expose its nodes through `NodeRef`.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant};
+
+use dist::Metric;
+
+// A cheap handle to a node of some tree layout.
+pub trait NodeRef<'a, T: 'a>: Copy {
+    fn word(&self) -> &'a [T];
+    // Distance to the parent node, 0 for the root.
+    fn edge(&self) -> usize;
+    fn is_deleted(&self) -> bool;
+    // Number of times the entry was inserted, see `DuplicatePolicy::Count`.
+    fn count(&self) -> usize;
+    fn child_count(&self) -> usize;
+    // Children are ordered by increasing edge distance.
+    fn child(&self, idx: usize) -> Self;
+}
+
+pub fn abs_diff(a: usize, b: usize) -> usize {
+    match a > b {
+        true => a - b,
+        false => b - a
+    }
+}
+
+// Index of the first child whose edge distance is not below `dist`.
+fn lower_bound<'a, T: 'a, N: NodeRef<'a, T>>(node: N, dist: usize) -> usize {
+    let mut low = 0;
+    let mut high = node.child_count();
+
+    while low < high {
+        let mid = low + (high - low) / 2;
+
+        match node.child(mid).edge() < dist {
+            true => low = mid + 1,
+            false => high = mid
+        };
+    }
+
+    low
+}
+
+// Returns the range of children whose edge distance lies within `dist` of `curr_dist`.
+pub fn child_window<'a, T: 'a, N: NodeRef<'a, T>>(node: N, curr_dist: usize, dist: usize) -> (usize, usize) {
+    let min_dist = curr_dist.saturating_sub(dist);
+    let max_dist = curr_dist.saturating_add(dist).saturating_add(1);
+
+    (lower_bound(node, min_dist), lower_bound(node, max_dist))
+}
+
+// How much work a range search did.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    // Number of calls to the metric.
+    pub distance_evaluations: usize,
+    // Number of nodes reached, tombstones included.
+    pub nodes_visited: usize,
+    // Number of children skipped because their edge distance fell outside the search window.
+    pub children_pruned: usize,
+    pub elapsed: Duration,
+}
+
+// Lazily walks a subtree with an explicit stack, yielding every live node within `max_dist` of `word` as it is found.
+pub struct SearchIter<'q, T: 'q, N, M: 'q> {
+    stack: Vec<N>,
+    word: &'q [T],
+    max_dist: usize,
+    metric: &'q M,
+    stats: SearchStats,
+}
+
+impl<'q, T, N, M> SearchIter<'q, T, N, M> {
+    pub fn new(root: N, word: &'q [T], max_dist: usize, metric: &'q M) -> SearchIter<'q, T, N, M> {
+        SearchIter {
+            stack: vec![root],
+            word,
+            max_dist,
+            metric,
+            stats: Default::default()
+        }
+    }
+
+    // Work done so far. `elapsed` is left to the caller, as the walk may be interleaved with other work.
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+}
+
+impl<'a, 'q, T: 'a, N: NodeRef<'a, T>, M: Metric<T>> Iterator for SearchIter<'q, T, N, M> {
+    type Item = (N, usize);
+
+    fn next(&mut self) -> Option<(N, usize)> {
+        while let Some(node) = self.stack.pop() {
+            let curr_dist = self.metric.distance(node.word(), self.word);
+            let (min_idx, max_idx) = child_window(node, curr_dist, self.max_dist);
+
+            self.stats.distance_evaluations += 1;
+            self.stats.nodes_visited += 1;
+            self.stats.children_pruned += node.child_count() - (max_idx - min_idx);
+
+            // Pushed in reverse so children are visited in edge distance order.
+            self.stack.extend((min_idx..max_idx).rev().map(|idx| node.child(idx)));
+
+            if curr_dist <= self.max_dist && !node.is_deleted() {
+                return Some((node, curr_dist));
+            }
+        }
+
+        None
+    }
+}
+
+// Collects every live node within `max_dist` of `word`, along with the work it took.
+pub fn search_with_stats<'a, T: 'a, N: NodeRef<'a, T>, M: Metric<T>>(root: N, word: &[T], max_dist: usize, metric: &M) -> (Vec<(N, usize)>, SearchStats) {
+    let start = Instant::now();
+    let mut nodes = SearchIter::new(root, word, max_dist, metric);
+    let results = nodes.by_ref().collect();
+    let mut stats = nodes.stats();
+
+    stats.elapsed = start.elapsed();
+    (results, stats)
+}
+
+// A node waiting to be visited by `nearest`, ordered so the smallest lower bound pops first.
+struct Candidate<N> {
+    bound: usize,
+    seq: usize,
+    node: N,
+}
+
+impl<N> PartialEq for Candidate<N> {
+    fn eq(&self, other: &Candidate<N>) -> bool {
+        self.bound == other.bound && self.seq == other.seq
+    }
+}
+
+impl<N> Eq for Candidate<N> {}
+
+impl<N> Ord for Candidate<N> {
+    fn cmp(&self, other: &Candidate<N>) -> Ordering {
+        (other.bound, other.seq).cmp(&(self.bound, self.seq))
+    }
+}
+
+impl<N> PartialOrd for Candidate<N> {
+    fn partial_cmp(&self, other: &Candidate<N>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// A match kept by `nearest`, ordered so the worst match pops first.
+struct Hit<N> {
+    dist: usize,
+    seq: usize,
+    node: N,
+}
+
+impl<N> PartialEq for Hit<N> {
+    fn eq(&self, other: &Hit<N>) -> bool {
+        self.dist == other.dist && self.seq == other.seq
+    }
+}
+
+impl<N> Eq for Hit<N> {}
+
+impl<N> Ord for Hit<N> {
+    fn cmp(&self, other: &Hit<N>) -> Ordering {
+        (self.dist, self.seq).cmp(&(other.dist, other.seq))
+    }
+}
+
+impl<N> PartialOrd for Hit<N> {
+    fn partial_cmp(&self, other: &Hit<N>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Returns up to `k` live nodes closest to `word`, ordered by increasing distance.
+// Nodes are visited best-first and the search radius shrinks to the k-th best distance found so far.
+pub fn nearest<'a, T: 'a, N: NodeRef<'a, T>, M: Metric<T>>(root: N, word: &[T], k: usize, metric: &M) -> Vec<(N, usize)> {
+    if k == 0 {
+        return vec![];
+    }
+
+    let mut seq = 0;
+    let mut queue = BinaryHeap::new();
+    // Grown as matches are found rather than sized from `k`, which may be far larger than the tree.
+    let mut hits: BinaryHeap<Hit<N>> = BinaryHeap::new();
+
+    queue.push(Candidate { bound: 0, seq, node: root });
+
+    while let Some(Candidate { bound, node, .. }) = queue.pop() {
+        let radius = match hits.len() < k {
+            true => usize::MAX,
+            false => hits.peek().unwrap().dist
+        };
+
+        if bound >= radius {
+            break;
+        }
+
+        let curr_dist = metric.distance(node.word(), word);
+
+        if curr_dist < radius && !node.is_deleted() {
+            seq += 1;
+            hits.push(Hit { dist: curr_dist, seq, node });
+
+            if hits.len() > k {
+                hits.pop();
+            }
+        }
+
+        let radius = match hits.len() < k {
+            true => usize::MAX,
+            false => hits.peek().unwrap().dist
+        };
+
+        let (min_idx, max_idx) = child_window(node, curr_dist, radius);
+
+        for idx in min_idx..max_idx {
+            let child = node.child(idx);
+            let bound = abs_diff(curr_dist, child.edge());
+
+            if bound < radius {
+                seq += 1;
+                queue.push(Candidate { bound, seq, node: child });
+            }
+        }
+    }
+
+    hits.into_sorted_vec().into_iter().map(|hit| (hit.node, hit.dist)).collect()
+}
+
+// Returns the live node closest to `word`, pruning with the best distance found so far.
+pub fn find_closest<'a, T: 'a, N: NodeRef<'a, T>, M: Metric<T>>(root: N, word: &[T], max_dist: usize, metric: &M) -> Option<(N, usize)> {
+    let mut best: Option<(N, usize)> = None;
+    // Nodes still to visit, with a lower bound on the distance of anything in their subtree.
+    let mut stack = vec![(root, 0)];
+
+    while let Some((node, bound)) = stack.pop() {
+        // Once a match is found, only strictly closer entries are of interest.
+        let mut limit = match best {
+            Some((_, 0)) => break,
+            Some((_, best_dist)) => best_dist - 1,
+            None => max_dist
+        };
+
+        if bound > limit {
+            continue;
+        }
+
+        let curr_dist = metric.distance(node.word(), word);
+
+        if curr_dist <= limit && !node.is_deleted() {
+            best = Some((node, curr_dist));
+
+            if curr_dist == 0 {
+                break;
+            }
+
+            limit = curr_dist - 1;
+        }
+
+        let (min_idx, max_idx) = child_window(node, curr_dist, limit);
+        let start = stack.len();
+
+        stack.extend((min_idx..max_idx).map(|idx| {
+            let child = node.child(idx);
+            (child, abs_diff(curr_dist, child.edge()))
+        }));
+
+        // Visit the children whose edge is closest to the current distance first, they are the most likely to improve the best match.
+        stack[start..].sort_by_key(|&(_, bound)| Reverse(bound));
+    }
+
+    best
+}
@@ -0,0 +1,153 @@
+// Shape statistics of a `BkTree`, for comparing metrics and insertion orders by how well the resulting tree can prune.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bktree::_BkTree;
+use dist::Metric;
+use search::NodeRef;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TreeStats {
+    // Number of nodes, tombstones included.
+    pub nodes: usize,
+    // Number of removed entries still routing searches.
+    pub tombstones: usize,
+    // Number of edges on the longest path from the root.
+    pub max_depth: usize,
+    // Average number of edges from the root over all nodes.
+    pub mean_depth: f64,
+    // For each level starting at the root, the number of nodes having a given number of children.
+    pub fanout_per_level: Vec<BTreeMap<usize, usize>>,
+    // Number of nodes below the root for each edge distance to their parent.
+    pub edge_distances: BTreeMap<usize, usize>,
+    // Words inserted at distance 0 from an existing entry: nodes at edge distance 0 plus the extra counts kept
+    // under `DuplicatePolicy::Count`.
+    pub duplicates: usize,
+}
+
+impl TreeStats {
+    fn collect<'a, T: 'a, N: NodeRef<'a, T>>(root: N) -> TreeStats {
+        let mut stats = TreeStats::default();
+        let mut depth_sum = 0;
+        let mut stack = vec![(root, 0)];
+
+        while let Some((node, level)) = stack.pop() {
+            stats.nodes += 1;
+            depth_sum += level;
+            stats.max_depth = stats.max_depth.max(level);
+
+            if stats.fanout_per_level.len() == level {
+                stats.fanout_per_level.push(BTreeMap::new());
+            }
+
+            *stats.fanout_per_level[level].entry(node.child_count()).or_default() += 1;
+
+            if level > 0 {
+                *stats.edge_distances.entry(node.edge()).or_default() += 1;
+
+                if node.edge() == 0 {
+                    stats.duplicates += 1;
+                }
+            }
+
+            match node.is_deleted() {
+                true => stats.tombstones += 1,
+                false => stats.duplicates += node.count() - 1
+            };
+
+            stack.extend((0..node.child_count()).map(|idx| (node.child(idx), level + 1)));
+        }
+
+        stats.mean_depth = depth_sum as f64 / stats.nodes as f64;
+        stats
+    }
+
+    // Average number of children over the nodes that have any.
+    pub fn mean_fanout(&self) -> f64 {
+        let (parents, children) = self.fanout_per_level.iter()
+            .flat_map(|level| level.iter())
+            .filter(|&(&fanout, _)| fanout > 0)
+            .fold((0, 0), |(parents, children), (&fanout, &nodes)| (parents + nodes, children + fanout * nodes));
+
+        match parents {
+            0 => 0.0,
+            _ => children as f64 / parents as f64
+        }
+    }
+}
+
+impl fmt::Display for TreeStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "nodes: {} ({} removed)", self.nodes, self.tombstones)?;
+        writeln!(f, "duplicates: {}", self.duplicates)?;
+        writeln!(f, "depth: max {}, mean {:.2}", self.max_depth, self.mean_depth)?;
+        writeln!(f, "fanout: mean {:.2}", self.mean_fanout())?;
+
+        for (level, fanouts) in self.fanout_per_level.iter().enumerate() {
+            let fanouts: Vec<String> = fanouts.iter().map(|(fanout, nodes)| format!("{}x{}", nodes, fanout)).collect();
+
+            writeln!(f, "  level {}: {}", level, fanouts.join(" "))?;
+        }
+
+        let edges: Vec<String> = self.edge_distances.iter().map(|(dist, nodes)| format!("{}:{}", dist, nodes)).collect();
+
+        writeln!(f, "edge distances: {}", edges.join(" "))
+    }
+}
+
+impl<T: Eq + Clone + Sized, M: Metric<T>> _BkTree<T, M> {
+    // Walks the whole tree to gather its shape statistics.
+    pub fn stats(&self) -> TreeStats {
+        match self.root() {
+            Some(root) => TreeStats::collect(root),
+            None => TreeStats::default()
+        }
+    }
+}
+
+#[test]
+fn stats_test() {
+    use bknode::DuplicatePolicy;
+    use bktree::BkTree;
+    use dist::convert_str;
+
+    let mut b: BkTree<char> = BkTree::new(None);
+
+    assert_eq!(b.stats(), TreeStats::default());
+
+    // foo -> { foo (0), fob (1), bar (3) -> { baz (1), foobar (3) } }
+    b.add_list(["foo", "bar", "baz", "fob", "foobar"].iter().map(convert_str).collect());
+    b.add(convert_str("foo"));
+
+    let stats = b.stats();
+
+    assert_eq!(stats.nodes, 6);
+    assert_eq!(stats.tombstones, 0);
+    assert_eq!(stats.max_depth, 2);
+    assert_eq!(stats.mean_depth, 7.0 / 6.0);
+    assert_eq!(stats.fanout_per_level.len(), 3);
+    assert_eq!(stats.fanout_per_level[0], vec![(3, 1)].into_iter().collect());
+    assert_eq!(stats.fanout_per_level[1], vec![(0, 2), (2, 1)].into_iter().collect());
+    assert_eq!(stats.fanout_per_level[2], vec![(0, 2)].into_iter().collect());
+    assert_eq!(stats.edge_distances, vec![(0, 1), (1, 2), (3, 2)].into_iter().collect());
+    assert_eq!(stats.duplicates, 1);
+    assert_eq!(stats.mean_fanout(), 2.5);
+    assert_eq!(stats.max_depth, b.depth());
+
+    b.set_duplicate_policy(DuplicatePolicy::Count);
+    b.add(convert_str("bar"));
+    b.add(convert_str("bar"));
+    assert!(b.remove(&convert_str("baz")));
+
+    let stats = b.stats();
+
+    assert_eq!(stats.nodes, 6);
+    assert_eq!(stats.tombstones, 1);
+    assert_eq!(stats.duplicates, 3);
+    assert!(stats.to_string().contains("nodes: 6 (1 removed)"));
+}
@@ -0,0 +1,65 @@
+/* Exercises the C interface through include/bkstring.h, built and run by tests/c_api.rs. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "bkstring.h"
+
+#define CHECK(cond) \
+    do { \
+        if (!(cond)) { \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+            return 1; \
+        } \
+    } while (0)
+
+int main(void) {
+    const char *words[] = { "book", "books", "cake", "boo", "boon", "cook", "cape", "cart", "brook", "bark" };
+    size_t i;
+    int found_book = 0;
+    BkTreeHandle *tree = bk_tree_new();
+
+    CHECK(tree != NULL);
+
+    for (i = 0; i < sizeof(words) / sizeof(words[0]); i++) {
+        CHECK(bk_tree_add_utf8(tree, words[i]) == 1);
+    }
+
+    CHECK(bk_tree_add_utf8(tree, "b\xc3\xb6ok") == 1);
+    CHECK(bk_tree_add_utf8(tree, "\xff") == -1);
+    CHECK(bk_tree_add_utf8(tree, NULL) == -1);
+    CHECK(bk_tree_len(tree) == 11);
+
+    BkMatches *matches = bk_tree_search(tree, "bok", 1);
+
+    CHECK(matches != NULL);
+    /* book, boo and b\xc3\xb6ok */
+    CHECK(matches->len == 3);
+
+    for (i = 0; i < matches->len; i++) {
+        CHECK(matches->matches[i].distance <= 1);
+
+        if (strcmp(matches->matches[i].word, "book") == 0) {
+            CHECK(matches->matches[i].distance == 1);
+            found_book = 1;
+        }
+    }
+
+    CHECK(found_book);
+    bk_matches_free(matches);
+
+    matches = bk_tree_search(tree, "zzzzzz", 2);
+    CHECK(matches != NULL && matches->len == 0);
+    bk_matches_free(matches);
+
+    CHECK(bk_tree_search(tree, NULL, 1) == NULL);
+    bk_tree_free(tree);
+
+    CHECK(bk_tree_new_with_metric("cosine") == NULL);
+    tree = bk_tree_new_with_metric("hamming");
+    CHECK(tree != NULL);
+    CHECK(bk_tree_add_utf8(tree, "abc") == 1);
+    bk_tree_free(tree);
+
+    return 0;
+}
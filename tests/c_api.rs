@@ -0,0 +1,68 @@
+// Checks the C header against src/ffi.rs and runs tests/c_api.c against the built library.
+
+extern crate cbindgen;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn crate_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+// Regenerate the header by running this test with BKSTRING_UPDATE_HEADER=1.
+#[test]
+fn header_test() {
+    let path = crate_dir().join("include").join("bkstring.h");
+    let mut generated = vec![];
+
+    cbindgen::generate(crate_dir()).unwrap().write(&mut generated);
+
+    if env::var_os("BKSTRING_UPDATE_HEADER").is_some() {
+        fs::write(&path, &generated).unwrap();
+    }
+
+    let committed = fs::read(&path).unwrap();
+
+    assert!(committed == generated, "include/bkstring.h is out of date with src/ffi.rs");
+}
+
+#[test]
+fn c_program_test() {
+    let tmp_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let target_dir = tmp_dir.join("c_api_target");
+    let lib_dir = target_dir.join("debug");
+    let program = tmp_dir.join("c_api");
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+    // Tests only get the rlib, so the shared library is built on the side. A separate target directory avoids
+    // waiting on the lock held by the cargo running this test.
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .arg("build").arg("--lib")
+        .arg("--manifest-path").arg(crate_dir().join("Cargo.toml"))
+        .arg("--target-dir").arg(&target_dir)
+        .status()
+        .unwrap();
+
+    assert!(status.success(), "failed to build the shared library");
+
+    let status = Command::new(compiler)
+        .arg("-Wall").arg("-Wextra").arg("-Werror")
+        .arg(crate_dir().join("tests").join("c_api.c"))
+        .arg("-I").arg(crate_dir().join("include"))
+        .arg("-L").arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lbkstring")
+        .arg("-o").arg(&program)
+        .status()
+        .unwrap();
+
+    assert!(status.success(), "failed to compile tests/c_api.c");
+
+    // Cargo puts target/debug on the library path of tests, where an older build of the library may sit, and that
+    // path is searched before the rpath.
+    let output = Command::new(&program).env("LD_LIBRARY_PATH", &lib_dir).output().unwrap();
+
+    assert!(output.status.success(), "tests/c_api.c failed: {}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,142 @@
+// Runs the bkstring command-line tool on a small word list.
+
+extern crate bkstring;
+extern crate serde_json;
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+use bkstring::dist::levenshtein_dist;
+
+const WORDS: &str = "book\nbooks\ncake\nboo\nboon\ncook\ncape\ncart\nbrook\nbark\n\nb\u{f6}ok\n";
+
+fn bkstring<S: AsRef<[u8]>>(args: &[&str], stdin: S) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bkstring"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(stdin.as_ref()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: Output) -> String {
+    assert!(output.status.success(), "bkstring failed: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn index_path(name: &str) -> String {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name).to_str().unwrap().to_string()
+}
+
+#[test]
+fn build_query_test() {
+    let words = index_path("cli_words.txt");
+    let index = index_path("cli_words.bk");
+
+    fs::write(&words, WORDS).unwrap();
+    stdout(bkstring(&["build", "--output", &index, &words], ""));
+
+    assert_eq!(stdout(bkstring(&["query", "--index", &index, "--max-dist", "1", "bok"], "")), "boo\t1\nbook\t1\nb\u{f6}ok\t1\n");
+    assert_eq!(stdout(bkstring(&["query", "--index", &index, "--max-dist", "0", "cart"], "")), "cart\t0\n");
+    assert_eq!(stdout(bkstring(&["query", "--index", &index, "--max-dist", "1", "zzzzzz"], "")), "");
+
+    // Entries holding a tab or a backslash are escaped, so every output line keeps two fields.
+    let tabs = index_path("cli_tabs.bk");
+
+    stdout(bkstring(&["build", "--output", &tabs, "-"], "a\tb\nc\\d\n"));
+    assert_eq!(stdout(bkstring(&["query", "--index", &tabs, "--max-dist", "3", "ab"], "")), "a\\tb\t1\nc\\\\d\t3\n");
+
+    let stats = stdout(bkstring(&["stats", "--index", &index], ""));
+
+    assert!(stats.starts_with("metric: levenshtein\nentries: 11\nnodes: 11 (0 removed)\n"), "{}", stats);
+}
+
+#[test]
+fn build_metric_test() {
+    let index = index_path("cli_hamming.bk");
+
+    stdout(bkstring(&["build", "--metric", "hamming", "--output", &index, "-"], WORDS));
+
+    assert_eq!(stdout(bkstring(&["query", "--index", &index, "--max-dist", "1", "boot"], "")), "book\t1\nboon\t1\n");
+    assert!(stdout(bkstring(&["stats", "--index", &index], "")).starts_with("metric: hamming\n"));
+}
+
+#[test]
+fn error_test() {
+    let words = index_path("cli_not_an_index.txt");
+
+    fs::write(&words, WORDS).unwrap();
+
+    for args in [&[][..], &["frobnicate"], &["build", "--metric", "cosine", "--output", "x"], &["build", "--output"],
+                 &["query", "--index", &words, "--max-dist", "1", "bok"], &["query", "--index", "/nonexistent", "--max-dist", "1", "bok"],
+                 &["stats", "--index", &words, "--max-dist", "1"]].iter() {
+        let output = bkstring(args, "");
+
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("bkstring: "), "{:?}", args);
+    }
+}
+
+#[test]
+fn match_test() {
+    let index = index_path("cli_match.bk");
+
+    stdout(bkstring(&["build", "--output", &index], WORDS));
+
+    let tsv = stdout(bkstring(&["match", "--index", &index, "--max-dist", "1"], "bookz\nzzzzzz\ncart\n"));
+
+    assert_eq!(tsv, "bookz\tbook\t1\nzzzzzz\t\t\ncart\tcart\t0\n");
+
+    let jsonl = stdout(bkstring(&["match", "--index", &index, "--max-dist", "1", "--format", "jsonl"], "bookz\nzz\"\\zz\n"));
+    let records: Vec<serde_json::Value> = jsonl.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+
+    assert_eq!(records, vec![
+        serde_json::json!({ "line": "bookz", "match": "book", "distance": 1 }),
+        serde_json::json!({ "line": "zz\"\\zz", "match": null, "distance": null })
+    ]);
+
+    // Separators inside a line are escaped so every output line keeps three fields.
+    let tsv = stdout(bkstring(&["match", "--index", &index, "--max-dist", "1"], "bo\tok\\\rz\ncart\r\n"));
+
+    assert_eq!(tsv, "bo\\tok\\\\\\rz\t\t\ncart\tcart\t0\n");
+
+    // A line that is not UTF-8 is matched with replacement characters instead of ending the stream.
+    let tsv = stdout(bkstring(&["match", "--index", &index, "--max-dist", "1"], &b"cak\xff\ncart\n"[..]));
+
+    assert_eq!(tsv, "cak\u{fffd}\tcake\t1\ncart\tcart\t0\n");
+
+    let output = bkstring(&["match", "--index", &index, "--max-dist", "1", "--format", "xml"], "");
+
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn match_order_test() {
+    let index = index_path("cli_match_order.bk");
+
+    stdout(bkstring(&["build", "--output", &index], WORDS));
+
+    let chars = |word: &str| word.chars().collect::<Vec<char>>();
+    // Enough lines to span several batches, each one edit away from an entry.
+    let words: Vec<&str> = WORDS.lines().filter(|word| !word.is_empty()).collect();
+    let lines: Vec<String> = (0..10_000).map(|idx| format!("{}{}", words[idx % words.len()], idx % 2)).collect();
+    let output = stdout(bkstring(&["match", "--index", &index, "--max-dist", "1", "--threads", "4"], &(lines.join("\n") + "\n")));
+    let output: Vec<&str> = output.lines().collect();
+
+    assert_eq!(output.len(), lines.len());
+
+    for (line, out) in lines.iter().zip(output) {
+        let fields: Vec<&str> = out.split('\t').collect();
+
+        assert_eq!(fields[0], line);
+        assert_eq!(fields[2], "1");
+        assert_eq!(levenshtein_dist(&chars(line), &chars(fields[1])), 1, "{}", out);
+    }
+}